@@ -0,0 +1,134 @@
+use std::f32::consts::{PI, TAU};
+
+use nih_plug::prelude::Enum;
+
+/// The number of points per revolution in the current-phase relation lookup table.
+pub const CPR_TABLE_SIZE: usize = 1024;
+/// The number of points in the user drawable wavetable.
+pub const USER_WAVETABLE_SIZE: usize = 64;
+/// How much the amount needs to change before the lookup table is rebuilt.
+const AMOUNT_EPSILON: f32 = 1e-3;
+
+/// The shape of the junction's current-phase relation.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CprShape {
+    /// The ideal tunnel junction, `sin(phase)`.
+    #[name = "Sine"]
+    Sine,
+    /// A highly transparent junction (SNS, point contacts). The amount sets the transparency.
+    #[name = "Skewed Sine"]
+    Skewed,
+    /// A second harmonic mixed in, like in 0-pi and d-wave junctions. The amount sets the mix.
+    #[name = "Harmonic Mix"]
+    Harmonics,
+    #[name = "Triangle"]
+    Triangle,
+    /// The ballistic junction at zero temperature.
+    #[name = "Sawtooth"]
+    Sawtooth,
+    /// The wavetable drawn in the editor.
+    #[name = "User Wavetable"]
+    Wavetable,
+}
+
+/// Maps the junction's phase to its supercurrent, normalized to a peak of 1.
+pub struct CurrentPhaseRelation {
+    shape: CprShape,
+    amount: f32,
+    table: [f32; CPR_TABLE_SIZE],
+    user_table: [f32; CPR_TABLE_SIZE],
+}
+
+impl Default for CurrentPhaseRelation {
+    fn default() -> Self {
+        Self {
+            shape: CprShape::Sine,
+            amount: 0.0,
+            table: [0.0; CPR_TABLE_SIZE],
+            user_table: [0.0; CPR_TABLE_SIZE],
+        }
+    }
+}
+
+impl CurrentPhaseRelation {
+    /// Rebuild the lookup table if the shape or amount changed. Changes to the amount smaller than
+    /// [`AMOUNT_EPSILON`] are ignored so a smoothed amount doesn't rebuild the table every time.
+    pub fn update(&mut self, shape: CprShape, amount: f32) {
+        if self.shape == shape && (self.amount - amount).abs() < AMOUNT_EPSILON {
+            return;
+        }
+        self.shape = shape;
+        self.amount = amount;
+        self.rebuild();
+    }
+
+    /// Set the user wavetable from one revolution's worth of points. Only the first
+    /// [`USER_WAVETABLE_SIZE`] points are used, fewer points leave the rest of the table at zero.
+    pub fn set_wavetable(&mut self, points: &[f32]) {
+        let mut padded = [0.0; USER_WAVETABLE_SIZE];
+        for (p, point) in padded.iter_mut().zip(points) {
+            *p = *point;
+        }
+        for (i, value) in self.user_table.iter_mut().enumerate() {
+            let position = i as f32 * USER_WAVETABLE_SIZE as f32 / CPR_TABLE_SIZE as f32;
+            let idx = position as usize;
+            let frac = position - idx as f32;
+            let a = padded[idx];
+            let b = padded[(idx + 1) % USER_WAVETABLE_SIZE];
+            *value = a + (b - a) * frac;
+        }
+        normalize(&mut self.user_table);
+        if self.shape == CprShape::Wavetable {
+            self.rebuild();
+        }
+    }
+
+    /// The normalized supercurrent at `phase`.
+    pub fn current(&self, phase: f32) -> f32 {
+        if self.shape == CprShape::Sine {
+            return phase.sin();
+        }
+
+        let position = phase.rem_euclid(TAU) / TAU * CPR_TABLE_SIZE as f32;
+        let idx = (position as usize).min(CPR_TABLE_SIZE - 1);
+        let frac = position - idx as f32;
+        let a = self.table[idx];
+        let b = self.table[(idx + 1) % CPR_TABLE_SIZE];
+        a + (b - a) * frac
+    }
+
+    fn rebuild(&mut self) {
+        if self.shape == CprShape::Wavetable {
+            self.table = self.user_table;
+            return;
+        }
+
+        let shape = self.shape;
+        // Fully transparent junctions turn into a sawtooth, stay just below that
+        let transparency = self.amount.clamp(0.0, 1.0) * 0.99;
+        let mix = self.amount.clamp(0.0, 1.0);
+        for (i, value) in self.table.iter_mut().enumerate() {
+            let phase = i as f32 / CPR_TABLE_SIZE as f32 * TAU;
+            *value = match shape {
+                CprShape::Sine | CprShape::Wavetable => phase.sin(),
+                CprShape::Skewed => {
+                    phase.sin() / (1.0 - transparency * (phase / 2.0).sin().powi(2)).sqrt()
+                }
+                CprShape::Harmonics => (1.0 - mix) * phase.sin() + mix * (2.0 * phase).sin(),
+                CprShape::Triangle => phase.sin().asin() * 2.0 / PI,
+                CprShape::Sawtooth => {
+                    let wrapped = (phase + PI).rem_euclid(TAU) - PI;
+                    wrapped / PI
+                }
+            };
+        }
+        normalize(&mut self.table);
+    }
+}
+
+fn normalize(table: &mut [f32]) {
+    let peak = table.iter().fold(0.0f32, |peak, v| peak.max(v.abs()));
+    if peak > f32::EPSILON {
+        table.iter_mut().for_each(|v| *v /= peak);
+    }
+}
@@ -1,22 +1,88 @@
+use std::cell::RefCell;
 use std::f32;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
 
+use crate::cpr::{CprShape, CurrentPhaseRelation, USER_WAVETABLE_SIZE};
+use crate::iv_curve::{iv_curve, IV_CURVE_RANGE};
+use crate::junction::JunctionSettings;
+use crate::metering::{LevelMeter, Metering, SCOPE_SIZE};
+use crate::morph::{self, MorphedValue};
+use crate::multiband::MAX_BANDS;
+use crate::presets::{self, Preset};
+use crate::ring_buffer::AtomicRingBuffer;
+use crate::snapshots::{Snapshots, NUM_SNAPSHOTS};
+use crate::spectrum::{SpectrumAnalyzer, SpectrumAveraging, SPECTRUM_FLOOR_DB};
+use crate::theme::EditorTheme;
+use crate::undo::{PresetChange, UndoHistory};
+use crate::widgets::{ParamSlider, ParamToggle};
+use crate::PHASE_HISTORY_SIZE;
 use crate::PrismatineParams;
-use atomic_refcell::AtomicRefCell;
-use nih_plug::nih_dbg;
+use iced_audio::{Knob, Normal, NormalParam};
+use nih_plug::{nih_dbg, nih_error, nih_log};
+use nih_plug::params::persist::PersistentField;
 use nih_plug::params::Param;
-use nih_plug::prelude::AtomicF32;
+use nih_plug::prelude::{AtomicF32, ParamPtr, Params};
+use nih_plug::util::gain_to_db;
 use nih_plug::{editor::Editor, prelude::GuiContext};
 use nih_plug_iced::core::Element;
-use nih_plug_iced::widget::{canvas, container, toggler, Column, Text};
-use nih_plug_iced::widgets as nih_widgets;
+use nih_plug_iced::widget::{
+    button, canvas, container, progress_bar, responsive, scrollable, slider, text, text_input, tooltip, Column,
+    Row, Space, Stack, Text,
+};
+use nih_plug_iced::widgets::ParamMessage;
 use nih_plug_iced::*;
 use nih_plug_iced::{create_iced_editor, IcedEditor, IcedState};
+use serde::{Deserialize, Serialize};
 use seven_segment_iced::canvas_segment::SevenSegmentCanvas;
 use seven_segment_iced::SevenSegmentStyle;
 
+/// The editor switches to a two column layout when it's at least this wide, before scaling.
+const WIDE_LAYOUT_MIN_WIDTH: f32 = 420.0;
+const MIN_WINDOW_SIZE: (u32, u32) = (200, 300);
+/// The size the editor opens with. This is well past [`WIDE_LAYOUT_MIN_WIDTH`] at 100%, so both
+/// columns have room for the displays and the knob rows. Both columns scroll, so the height only
+/// needs to fit on smaller screens.
+const DEFAULT_WINDOW_SIZE: (u32, u32) = (900, 720);
+const MAX_WINDOW_SIZE: (u32, u32) = (2400, 1800);
+/// The base text size, before scaling.
+const TEXT_SIZE: f32 = 14.0;
+/// The height of the seven segment displays, before scaling.
+const SEGMENT_DISPLAY_HEIGHT: f32 = 100.0;
+/// The diameter of the knobs in the knob layout, before scaling.
+const KNOB_SIZE: f32 = 40.0;
+/// The width of a knob with its label, before scaling.
+const KNOB_CELL_WIDTH: f32 = 64.0;
+/// The UI scales the user can step through.
+const UI_SCALES: [f32; 6] = [0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+/// The sections the top level parameters are sorted into, by parameter ID, in display order.
+/// Parameters in nested groups get a section named after their group, and anything not listed here
+/// ends up in a final "Other" section, so new parameters always show up.
+const SECTIONS: &[(&str, &[&str])] = &[
+    (
+        "Junction",
+        &[
+            "phase_gain",
+            "I_c",
+            "invert_phase",
+            "cpr_shape",
+            "cpr_amount",
+            "phase_steps",
+            "phase_hysteresis",
+            "phase_offset",
+        ],
+    ),
+    ("Multiband", &["num_bands", "crossover_1", "crossover_2", "crossover_3"]),
+    ("Tempo Sync", &["phase_sync", "lfo_rate", "lfo_depth"]),
+    ("Feedback", &["feedback", "feedback_delay"]),
+    ("Emphasis", &["emphasis_freq", "emphasis_amount"]),
+    ("Spectral", &["remove_dc", "fft_order", "freeze", "freeze_phase"]),
+    ("Post EQ", &["post_eq", "post_eq_mode", "low_cut", "high_cut", "tilt"]),
+    ("Morph", &["morph"]),
+];
+
 pub(crate) fn default_state() -> Arc<IcedState> {
-    IcedState::from_size(200, 500)
+    IcedState::from_size(DEFAULT_WINDOW_SIZE.0, DEFAULT_WINDOW_SIZE.1)
 }
 
 pub(crate) fn create(
@@ -26,25 +92,178 @@ pub(crate) fn create(
     create_iced_editor::<PrismatineEditor>(editor_state, params)
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 enum Message {
     /// Update a parameter's value.
-    ParamUpdate(nih_widgets::ParamMessage),
-    SwitchInvPhase(bool)
+    ParamUpdate(ParamMessage),
+    /// Collapse or expand the section at this index.
+    ToggleSection(usize),
+    /// Set a point of the user wavetable.
+    DrawWavetable(usize, f32),
+    /// Switch the second row of displays to the next [`ReadoutMode`].
+    CycleReadoutMode,
+    CycleSpectrumAveraging,
+    /// Grow or shrink the window by this many logical pixels.
+    ResizeWindowBy(f32, f32),
+    /// Step the UI scale up or down through [`UI_SCALES`].
+    StepScale(i32),
+    /// Switch to the next [`EditorTheme`].
+    CycleTheme,
+    /// Switch between the slider and the knob [`ControlLayout`].
+    CycleControlLayout,
+    /// Load the preset at this index in the editor's preset list.
+    LoadPreset(usize),
+    /// Load the next or previous preset that matches the category and search filters.
+    StepPreset(i32),
+    /// Filter the preset list by the next category, or show all categories again.
+    CyclePresetCategory,
+    /// Only list presets whose name contains this text.
+    SearchPresets(String),
+    /// Edit the name the current settings get saved under.
+    EditPresetName(String),
+    /// Save the current settings as a user preset under the edited name.
+    SavePreset,
+    /// Revert the last parameter edit made in the editor.
+    Undo,
+    Redo,
+    /// Store the current settings as the A (`false`) or B (`true`) end of the morph.
+    StoreMorphTarget(bool),
+    /// Set a parameter's normalized value at the A (`false`) or B (`true`) end of the morph.
+    SetMorphTarget(ParamPtr, bool, f32),
+    ClearMorphTargets,
+    /// Switch to another comparison slot.
+    SwitchSnapshot(usize),
+    /// Copy the current settings to another comparison slot.
+    CopySnapshot(usize),
+}
+
+/// How the continuous parameters are shown, saved with the plugin's state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ControlLayout {
+    /// A full width slider for every parameter.
+    #[default]
+    Sliders,
+    /// Rows of compact knobs.
+    Knobs,
+}
+
+impl ControlLayout {
+    fn next(self) -> Self {
+        match self {
+            ControlLayout::Sliders => ControlLayout::Knobs,
+            ControlLayout::Knobs => ControlLayout::Sliders,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ControlLayout::Sliders => "sliders",
+            ControlLayout::Knobs => "knobs",
+        }
+    }
+}
+
+/// What the second row of seven segment displays shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ReadoutMode {
+    /// The number of flux quanta the phase has slipped, negative for backwards rotation.
+    #[default]
+    Revolutions,
+    /// The junction voltage as the phase change in kilo radians per second.
+    Voltage,
+}
+
+impl ReadoutMode {
+    fn next(self) -> Self {
+        match self {
+            ReadoutMode::Revolutions => ReadoutMode::Voltage,
+            ReadoutMode::Voltage => ReadoutMode::Revolutions,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ReadoutMode::Revolutions => "revolutions",
+            ReadoutMode::Voltage => "voltage (krad/s)",
+        }
+    }
+}
+
+/// A collapsible group of parameter controls.
+struct Section {
+    name: String,
+    /// The parameters' IDs and pointers, in display order.
+    params: Vec<(String, ParamPtr)>,
+    expanded: bool,
+}
+
+impl Section {
+    fn new(name: impl Into<String>) -> Self {
+        Section { name: name.into(), params: Vec::new(), expanded: true }
+    }
+
+    /// Sort every parameter into a section, see [`SECTIONS`].
+    fn from_params(params: &PrismatineParams) -> Vec<Section> {
+        let mut sections: Vec<Section> = SECTIONS.iter().map(|(name, _)| Section::new(*name)).collect();
+        let mut groups: Vec<Section> = Vec::new();
+        let mut other = Section::new("Other");
+        for (id, ptr, group) in params.param_map() {
+            if !group.is_empty() {
+                match groups.iter_mut().find(|section| section.name == group) {
+                    Some(section) => section.params.push((id, ptr)),
+                    None => {
+                        let mut section = Section::new(group);
+                        section.params.push((id, ptr));
+                        groups.push(section);
+                    }
+                }
+            } else if let Some(idx) = SECTIONS.iter().position(|(_, ids)| ids.contains(&id.as_str())) {
+                sections[idx].params.push((id, ptr));
+            } else {
+                other.params.push((id, ptr));
+            }
+        }
+        for section in &mut sections {
+            let (_, ids) = SECTIONS.iter().find(|(name, _)| *name == section.name).unwrap();
+            section.params.sort_by_key(|(id, _)| ids.iter().position(|listed| *listed == id.as_str()));
+        }
+        // Array groups like the per band switches come after the named groups in the parameter
+        // list, this puts "Band 1" before "Band 2" again
+        groups.sort_by(|a, b| a.name.cmp(&b.name));
+
+        sections.extend(groups);
+        sections.push(other);
+        sections.retain(|section| !section.params.is_empty());
+        sections
+    }
 }
 
 struct PrismatineEditor {
     params: PrismatineEditorParams,
     context: Arc<dyn GuiContext>,
 
-    I_c_slider_state: Arc<AtomicRefCell<nih_widgets::param_slider::State>>,
-    phase_gain_slider_state: Arc<AtomicRefCell<nih_widgets::param_slider::State>>,
+    sections: Vec<Section>,
+    history: UndoHistory,
+    /// The factory presets followed by the user's presets.
+    presets: Vec<Preset>,
+    /// Only list presets in this category, or all presets when this is `None`.
+    preset_category: Option<String>,
+    preset_search: String,
+    /// The name in the save field, not to be confused with the loaded preset's name.
+    save_name: String,
+    readout_mode: ReadoutMode,
+    spectrum_averaging: SpectrumAveraging,
 }
 
 #[derive(Clone)]
 pub struct PrismatineEditorParams {
     pub prismatine_params: Arc<PrismatineParams>,
     pub phase: Arc<[AtomicF32; 2]>,
+    pub phase_history: Arc<[AtomicRingBuffer<PHASE_HISTORY_SIZE>; 2]>,
+    pub revolutions: Arc<[AtomicI64; 2]>,
+    pub voltage: Arc<[AtomicF32; 2]>,
+    pub band_meters: Arc<[AtomicF32; MAX_BANDS]>,
+    pub metering: Arc<Metering>,
 }
 
 impl IcedEditor for PrismatineEditor {
@@ -56,11 +275,18 @@ impl IcedEditor for PrismatineEditor {
         params: Self::InitializationFlags,
         context: Arc<dyn GuiContext>,
     ) -> (Self, Task<Self::Message>) {
+        let sections = Section::from_params(&params.prismatine_params);
         let editor = PrismatineEditor {
             params,
             context,
-            I_c_slider_state: Default::default(),
-            phase_gain_slider_state: Default::default(),
+            sections,
+            history: UndoHistory::default(),
+            presets: presets::factory_presets().into_iter().chain(presets::load_user_presets()).collect(),
+            preset_category: None,
+            preset_search: String::new(),
+            save_name: String::new(),
+            readout_mode: ReadoutMode::default(),
+            spectrum_averaging: SpectrumAveraging::default(),
         };
 
         (editor, Task::none())
@@ -70,20 +296,159 @@ impl IcedEditor for PrismatineEditor {
         self.context.as_ref()
     }
 
+    fn theme(&self) -> Theme {
+        self.editor_theme().iced_theme()
+    }
+
+    fn subscription(&self, _window_subs: &mut WindowSubs<Self::Message>) -> Subscription<Self::Message> {
+        // A focused text field captures the key presses, so typing in the preset search or the
+        // preset name doesn't undo anything
+        event::listen_with(|event, status, _window| match (event, status) {
+            (Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }), event::Status::Ignored) => {
+                undo_shortcut(key, modifiers)
+            }
+            _ => None,
+        })
+    }
+
     fn update(
         &mut self,
         //window: &mut WindowQueue,
         message: Self::Message,
     ) -> Task<Self::Message> {
         match message {
-            Message::ParamUpdate(message) => self.handle_param_message(message),
-            Message::SwitchInvPhase(value) => {
-                unsafe {
-                    self.context.raw_begin_set_parameter(self.params.prismatine_params.invert_phase.as_ptr());
-                    self.context.raw_set_parameter_normalized(self.params.prismatine_params.invert_phase.as_ptr(), match value {true => 1.0, _ => 0.0});
-                    self.context.raw_end_set_parameter(self.params.prismatine_params.invert_phase.as_ptr());
+            Message::ParamUpdate(message) => self.send_param_message(message),
+            Message::Undo => {
+                if let Some(step) = self.history.undo() {
+                    for change in step.changes.iter().rev() {
+                        self.restore_param(change.param, change.before);
+                    }
+                    if let Some(preset) = step.preset {
+                        self.restore_preset_fields(&preset.before);
+                    }
+                }
+            }
+            Message::Redo => {
+                if let Some(step) = self.history.redo() {
+                    for change in &step.changes {
+                        self.restore_param(change.param, change.after);
+                    }
+                    if let Some(preset) = step.preset {
+                        self.restore_preset_fields(&preset.after);
+                    }
+                }
+            }
+            Message::ToggleSection(idx) => {
+                if let Some(section) = self.sections.get_mut(idx) {
+                    section.expanded = !section.expanded;
+                }
+            }
+            Message::DrawWavetable(idx, value) => {
+                let params = &self.params.prismatine_params;
+                if let Some(point) = params.cpr_wavetable.write().unwrap().get_mut(idx) {
+                    *point = value;
+                }
+                params.cpr_wavetable_changed.store(true, Ordering::Release);
+            }
+            Message::CycleReadoutMode => self.readout_mode = self.readout_mode.next(),
+            Message::CycleSpectrumAveraging => self.spectrum_averaging = self.spectrum_averaging.next(),
+            Message::ResizeWindowBy(dx, dy) => {
+                let (width, height) = self.params.prismatine_params.editor_state.size();
+                self.resize_window(width as f32 + dx, height as f32 + dy);
+            }
+            Message::LoadPreset(idx) => self.load_preset(idx),
+            Message::StepPreset(direction) => {
+                let matches = self.matching_presets();
+                if !matches.is_empty() {
+                    let current = self.current_preset_name();
+                    let next = match matches.iter().position(|idx| self.presets[*idx].name == current) {
+                        Some(pos) => (pos as i32 + direction).rem_euclid(matches.len() as i32) as usize,
+                        None if direction < 0 => matches.len() - 1,
+                        None => 0,
+                    };
+                    self.load_preset(matches[next]);
+                }
+            }
+            Message::CyclePresetCategory => {
+                let mut categories: Vec<&str> = Vec::new();
+                for preset in &self.presets {
+                    if !categories.contains(&preset.category.as_str()) {
+                        categories.push(&preset.category);
+                    }
+                }
+                let next = match &self.preset_category {
+                    None => categories.first(),
+                    Some(current) => categories
+                        .iter()
+                        .position(|category| category == current)
+                        .and_then(|pos| categories.get(pos + 1)),
+                };
+                self.preset_category = next.map(|category| category.to_string());
+            }
+            Message::SearchPresets(search) => self.preset_search = search,
+            Message::EditPresetName(name) => self.save_name = name,
+            Message::SavePreset => self.save_preset(),
+            Message::StoreMorphTarget(b) => {
+                let params = &self.params.prismatine_params;
+                let amount = params.morph.modulated_plain_value();
+                let mut targets = params.morph_targets.write().unwrap();
+                if targets.is_morphing() && morph::end_at(amount).is_none() {
+                    return Task::none();
+                }
+                let current = Preset::capture(params, if b { "B" } else { "A" }, "Morph");
+                targets.store(current, b, amount, params);
+                params.morph_targets_changed.store(true, Ordering::Release);
+            }
+            Message::SetMorphTarget(param, b, normalized) => {
+                let params = &self.params.prismatine_params;
+                let morphed = params.morph_targets.write().unwrap().set_end_value(param, b, normalized, params);
+                params.morph_targets_changed.store(true, Ordering::Release);
+                // Once both ends agree the parameter follows its control again, which should sound
+                // the same
+                if !morphed {
+                    self.restore_param(param, normalized);
+                }
+            }
+            Message::ClearMorphTargets => {
+                let params = &self.params.prismatine_params;
+                params.morph_targets.write().unwrap().clear();
+                params.morph_targets_changed.store(true, Ordering::Release);
+            }
+            Message::SwitchSnapshot(slot) => {
+                let params = &self.params.prismatine_params;
+                let current = Preset::capture(params, "", "");
+                let snapshot = params.snapshots.write().unwrap().switch_to(slot, current);
+                if let Some(snapshot) = snapshot {
+                    self.restore_snapshot(&snapshot);
+                }
+            }
+            Message::CopySnapshot(slot) => {
+                let params = &self.params.prismatine_params;
+                let current = Preset::capture(params, "", "");
+                params.snapshots.write().unwrap().copy_to(slot, current);
+            }
+            Message::CycleTheme => {
+                let mut theme = self.params.prismatine_params.editor_theme.write().unwrap();
+                *theme = theme.next();
+            }
+            Message::CycleControlLayout => {
+                let mut layout = self.params.prismatine_params.editor_layout.write().unwrap();
+                *layout = layout.next();
+            }
+            Message::StepScale(direction) => {
+                let old_scale = self.scale();
+                let current = UI_SCALES
+                    .iter()
+                    .position(|scale| *scale >= old_scale)
+                    .unwrap_or(UI_SCALES.len() - 1) as i32;
+                let new_scale = UI_SCALES[(current + direction).clamp(0, UI_SCALES.len() as i32 - 1) as usize];
+                if new_scale != old_scale {
+                    *self.params.prismatine_params.editor_scale.write().unwrap() = new_scale;
+                    // Keep the layout the same by scaling the window along with its contents
+                    let (width, height) = self.params.prismatine_params.editor_state.size();
+                    let ratio = new_scale / old_scale;
+                    self.resize_window(width as f32 * ratio, height as f32 * ratio);
                 }
-                
             }
         }
 
@@ -91,67 +456,1237 @@ impl IcedEditor for PrismatineEditor {
     }
 
     fn view(&self) -> Element<'_, Self::Message, Theme, Renderer> {
-        let phase_left = self.params.phase[0]
-            .load(std::sync::atomic::Ordering::Relaxed)
-            .rem_euclid(f32::consts::PI)
-            .to_degrees();
-        let phase_right = self.params.phase[1]
-            .load(std::sync::atomic::Ordering::Relaxed)
-            .rem_euclid(f32::consts::PI)
-            .to_degrees();
-
-        Column::new().spacing(5.0)
+        let scale = self.scale();
+        let theme = self.editor_theme();
+        let sections = responsive(move |size| {
+            if size.width >= WIDE_LAYOUT_MIN_WIDTH * scale {
+                Row::new()
+                    .spacing(10.0 * scale)
+                    .push(scrollable(self.display_section()).width(Length::FillPortion(1)))
+                    .push(scrollable(self.control_section()).width(Length::FillPortion(1)))
+                    .into()
+            } else {
+                scrollable(
+                    Column::new()
+                        .spacing(5.0 * scale)
+                        .push(self.display_section())
+                        .push(self.control_section()),
+                )
+                .into()
+            }
+        });
+
+        Column::new().spacing(5.0 * scale)
             .push(Text::new("Prismatine")
-                        .size(30.0)
+                        .size(30.0 * scale)
                         .font(Font::with_name("NotoSans"))
                         .center()
                         .width(Length::Fill))
+            .push(container(sections).height(Length::Fill))
+            .push(
+                Row::new()
+                    .spacing(5.0 * scale)
+                    .push(button(self.label("-")).on_press(Message::StepScale(-1)))
+                    .push(self.label(format!("{:.0}%", scale * 100.0)))
+                    .push(button(self.label("+")).on_press(Message::StepScale(1)))
+                    .push(button(self.label(theme.label())).on_press(Message::CycleTheme))
+                    .push(button(self.label(self.control_layout().label())).on_press(Message::CycleControlLayout))
+                    .push(Space::with_width(Length::Fill))
+                    .push(canvas(ResizeGrip { theme }).width(16.0 * scale).height(16.0 * scale)),
+            )
+            .into()
+    }
+}
+
+impl PrismatineEditor {
+    /// The user's UI scale, on top of the host's HiDPI scaling.
+    fn scale(&self) -> f32 {
+        *self.params.prismatine_params.editor_scale.read().unwrap()
+    }
+
+    fn control_layout(&self) -> ControlLayout {
+        *self.params.prismatine_params.editor_layout.read().unwrap()
+    }
+
+    fn editor_theme(&self) -> EditorTheme {
+        *self.params.prismatine_params.editor_theme.read().unwrap()
+    }
+
+    fn label<'a>(&self, content: impl text::IntoFragment<'a>) -> Text<'a, Theme, Renderer> {
+        Text::new(content).size(TEXT_SIZE * self.scale())
+    }
+
+    /// Resize the window and store the new size in the editor state, so it's restored with the
+    /// plugin's state.
+    fn resize_window(&self, width: f32, height: f32) {
+        let (min_width, min_height) = MIN_WINDOW_SIZE;
+        let (max_width, max_height) = MAX_WINDOW_SIZE;
+        let width = (width.round() as u32).clamp(min_width, max_width);
+        let height = (height.round() as u32).clamp(min_height, max_height);
+        // `request_resize()` asks the host for the size stored in the editor state. The state only
+        // has a setter through its persistent field, which copies the size from another state.
+        let state = Arc::into_inner(IcedState::from_size(width, height)).expect("the state was just created");
+        self.params.prismatine_params.editor_state.set(state);
+        self.context.request_resize();
+    }
+
+    /// The name of the last loaded or saved preset.
+    fn current_preset_name(&self) -> String {
+        self.params.prismatine_params.editor_preset.read().unwrap().clone()
+    }
+
+    /// The indices of the presets that match the category and search filters.
+    fn matching_presets(&self) -> Vec<usize> {
+        let search = self.preset_search.trim().to_lowercase();
+        self.presets
+            .iter()
+            .enumerate()
+            .filter(|(_, preset)| self.preset_category.as_ref().is_none_or(|category| preset.category == *category))
+            .filter(|(_, preset)| preset.name.to_lowercase().contains(&search))
+            .map(|(idx, _)| idx)
+            .collect()
+    }
+
+    /// Load the preset at `idx` in the preset list and remember its name for the browser.
+    fn load_preset(&mut self, idx: usize) {
+        let Some(preset) = self.presets.get(idx).cloned() else {
+            return;
+        };
+
+        self.apply_preset(&preset);
+    }
+
+    /// Send the preset's values to the host as one gesture per parameter, then restore its fields
+    /// and name. The host passes the new values to the plugin, so they go through the usual
+    /// smoothing. This is a single undo step, which also brings back the old fields and name.
+    fn apply_preset(&mut self, preset: &Preset) {
+        let params = self.params.prismatine_params.clone();
+        let before = Preset::capture(&params, self.current_preset_name(), "");
+        self.history.begin_group();
+        for (ptr, normalized) in preset.normalized_values(&params) {
+            self.send_param_message(ParamMessage::BeginSetParameter(ptr));
+            self.send_param_message(ParamMessage::SetParameterNormalized(ptr, normalized));
+            self.send_param_message(ParamMessage::EndSetParameter(ptr));
+        }
+        self.restore_preset_fields(preset);
+        self.history.end_group(Some(PresetChange {
+            before,
+            after: preset.clone(),
+        }));
+    }
+
+    /// Restore a preset's fields and show its name in the browser, for loading it and for undoing
+    /// or redoing a load.
+    fn restore_preset_fields(&self, preset: &Preset) {
+        let params = &self.params.prismatine_params;
+        preset.apply_fields(params);
+        *params.editor_preset.write().unwrap() = preset.name.clone();
+    }
+
+    /// Switch over to a comparison slot's settings, through the smoothers like a preset. This stays
+    /// out of the undo history, since undoing it would bring back the old slot's settings without
+    /// switching back to that slot.
+    fn restore_snapshot(&self, snapshot: &Preset) {
+        let params = &self.params.prismatine_params;
+        for (ptr, normalized) in snapshot.normalized_values(params) {
+            self.restore_param(ptr, normalized);
+        }
+        snapshot.apply_fields(params);
+    }
+
+    /// Send a parameter change to the host and add it to the undo history.
+    fn send_param_message(&mut self, message: ParamMessage) {
+        self.history.record(message);
+        self.handle_param_message(message);
+    }
+
+    /// Set a parameter for undo or redo, without recording it in the history.
+    fn restore_param(&self, param: ParamPtr, normalized: f32) {
+        self.handle_param_message(ParamMessage::BeginSetParameter(param));
+        self.handle_param_message(ParamMessage::SetParameterNormalized(param, normalized));
+        self.handle_param_message(ParamMessage::EndSetParameter(param));
+    }
+
+    /// Save the current settings as a user preset under the name in the save field.
+    fn save_preset(&mut self) {
+        let name = self.save_name.trim();
+        if name.is_empty() {
+            return;
+        }
+
+        let preset = Preset::capture(&self.params.prismatine_params, name, presets::USER_CATEGORY);
+        match presets::save_user_preset(&preset) {
+            Ok(path) => nih_log!("Saved preset to {}", path.display()),
+            Err(err) => {
+                nih_error!("Could not save preset '{}': {err}", preset.name);
+                return;
+            }
+        }
+
+        *self.params.prismatine_params.editor_preset.write().unwrap() = preset.name.clone();
+        self.save_name.clear();
+        // The file replaced another preset if their names map to the same file
+        match self
+            .presets
+            .iter_mut()
+            .find(|existing| existing.category == presets::USER_CATEGORY && existing.file_name() == preset.file_name())
+        {
+            Some(existing) => *existing = preset,
+            None => self.presets.push(preset),
+        }
+    }
+
+    /// Previous and next buttons, the category and search filters, the matching presets and the
+    /// field for saving a user preset.
+    fn preset_browser(&self) -> Column<'_, Message, Theme, Renderer> {
+        let scale = self.scale();
+        let current = self.current_preset_name();
+        let mut list = Column::new().spacing(2.0 * scale);
+        for idx in self.matching_presets() {
+            let preset = &self.presets[idx];
+            let entry = button(self.label(format!("{}: {}", preset.category, preset.name)))
+                .on_press(Message::LoadPreset(idx))
+                .width(Length::Fill);
+            list = list.push(if preset.name == current {
+                entry.style(button::primary)
+            } else {
+                entry.style(button::text)
+            });
+        }
+
+        Column::new().spacing(5.0 * scale)
             .push(
-                canvas(SevenSegmentCanvas::new(
-                    seven_segment_iced::glyph::string_with_decimals_to_segment(format!(
-                        "{phase_left:0>5.1}"
-                    )),
-                    4,
-                    SevenSegmentStyle {
-                        background_color: Color::from_rgb(0.047, 0.067, 0.09),
-                        segment_color: Color::from_rgb(0.69, 1.0, 0.996),
-                        off_color: None, //Color or inactive segments
-                        margin_frac: 1.0 / 15.0,
-                        aspect_ratio: 6.9,
-                        line_margin_frac: 1.0 / 30.0,
-                        dot_size_frac: 1.0 / 15.0,
-                    },
-                ))
-                .width(Length::Fill),
+                Row::new().spacing(5.0 * scale)
+                    .push(button(self.label("<")).on_press(Message::StepPreset(-1)))
+                    .push(self.label(if current.is_empty() { String::from("no preset") } else { current }).width(Length::Fill).center())
+                    .push(button(self.label(">")).on_press(Message::StepPreset(1))),
             )
             .push(
-                canvas(SevenSegmentCanvas::new(
-                    seven_segment_iced::glyph::string_with_decimals_to_segment(format!(
-                        "{phase_right:0>5.1}"
-                    )),
-                    4,
-                    SevenSegmentStyle {
-                        background_color: Color::from_rgb(0.047, 0.067, 0.09),
-                        segment_color: Color::from_rgb(0.69, 1.0, 0.996),
-                        off_color: None, //Color or inactive segments
-                        margin_frac: 1.0 / 15.0,
-                        aspect_ratio: 6.9,
-                        line_margin_frac: 1.0 / 30.0,
-                        dot_size_frac: 1.0 / 15.0,
-                    },
-                ))
-                .width(Length::Fill),
+                Row::new().spacing(5.0 * scale)
+                    .push(
+                        button(self.label(self.preset_category.as_deref().unwrap_or("all")))
+                            .on_press(Message::CyclePresetCategory),
+                    )
+                    .push(
+                        text_input("search", &self.preset_search)
+                            .on_input(Message::SearchPresets)
+                            .size(TEXT_SIZE * scale),
+                    ),
             )
-            .push(Text::new("phase gain").width(Length::Fill).center())
-            .push(container(nih_plug_iced::widgets::ParamSlider::new(self.phase_gain_slider_state.clone(), &self.params.prismatine_params.phase_gain).map(Message::ParamUpdate)).width(Length::Fill).center_x(Length::Fill))
-            .push(Text::new("critical current").width(Length::Fill).center())
-            .push(container(nih_plug_iced::widgets::ParamSlider::new(self.I_c_slider_state.clone(), &self.params.prismatine_params.I_c).map(Message::ParamUpdate)).width(Length::Fill).center_x(Length::Fill))
-            .push(container(
-                toggler(self.params.prismatine_params.invert_phase.value())
-                .on_toggle(Message::SwitchInvPhase)
-                .label("Invert phase mode")
-                .width(Length::Fill)
-            ).width(Length::Fill))
+            .push(scrollable(list).height(100.0 * scale))
+            .push(
+                Row::new().spacing(5.0 * scale)
+                    .push(
+                        text_input("preset name", &self.save_name)
+                            .on_input(Message::EditPresetName)
+                            .on_submit(Message::SavePreset)
+                            .size(TEXT_SIZE * scale),
+                    )
+                    .push(button(self.label("save")).on_press(Message::SavePreset)),
+            )
+    }
+
+    fn history_controls(&self) -> Row<'_, Message, Theme, Renderer> {
+        let scale = self.scale();
+        Row::new().spacing(5.0 * scale)
+            .push(
+                button(self.label("undo").width(Length::Fill).center())
+                    .on_press_maybe(self.history.can_undo().then_some(Message::Undo))
+                    .width(Length::Fill),
+            )
+            .push(
+                button(self.label("redo").width(Length::Fill).center())
+                    .on_press_maybe(self.history.can_redo().then_some(Message::Redo))
+                    .width(Length::Fill),
+            )
+    }
+
+    /// A button per comparison slot, highlighting the active one, and buttons to copy the current
+    /// settings to the other slots.
+    fn snapshot_controls(&self) -> Column<'_, Message, Theme, Renderer> {
+        let scale = self.scale();
+        let snapshots = self.params.prismatine_params.snapshots.read().unwrap();
+        let mut slots = Row::new().spacing(5.0 * scale);
+        let mut copies = Row::new().spacing(5.0 * scale).push(self.label("copy to"));
+        for slot in 0..NUM_SNAPSHOTS {
+            let label = Snapshots::label(slot);
+            let style = if slot == snapshots.active() {
+                button::primary
+            } else if snapshots.is_filled(slot) {
+                button::secondary
+            } else {
+                button::text
+            };
+            slots = slots.push(
+                button(self.label(label.to_string()).width(Length::Fill).center())
+                    .style(style)
+                    .on_press(Message::SwitchSnapshot(slot))
+                    .width(Length::Fill),
+            );
+            if slot != snapshots.active() {
+                copies = copies.push(button(self.label(label.to_string())).on_press(Message::CopySnapshot(slot)));
+            }
+        }
+
+        Column::new().spacing(5.0 * scale).push(slots).push(copies)
+    }
+
+    /// The displays, meters and visualizers. This is the left column in the wide layout.
+    fn display_section(&self) -> Column<'_, Message, Theme, Renderer> {
+        let scale = self.scale();
+        let theme = self.editor_theme();
+        // The phase is kept within [-pi, pi), so this covers the whole circle without folding it
+        let phase_left = self.params.phase[0].load(std::sync::atomic::Ordering::Relaxed).to_degrees();
+        let phase_right = self.params.phase[1].load(std::sync::atomic::Ordering::Relaxed).to_degrees();
+
+        Column::new().spacing(5.0 * scale)
+            .push(segment_display(format!("{phase_left:>6.1}"), theme, scale))
+            .push(segment_display(format!("{phase_right:>6.1}"), theme, scale))
+            .push(
+                canvas(PhaseCircle { phase: &self.params.phase, history: &self.params.phase_history, theme })
+                    .width(Length::Fill)
+                    .height(100.0 * scale),
+            )
+            .push(level_meter(self.label("in"), &self.params.metering.input, scale))
+            .push(level_meter(self.label("out"), &self.params.metering.output, scale))
+            .push(
+                canvas(Oscilloscope { metering: &self.params.metering, theme })
+                    .width(Length::Fill)
+                    .height(80.0 * scale),
+            )
+            .push(
+                canvas(SpectrumView { metering: &self.params.metering, averaging: self.spectrum_averaging, theme })
+                    .width(Length::Fill)
+                    .height(100.0 * scale),
+            )
+            .push(
+                button(self.label(self.spectrum_averaging.label()).width(Length::Fill).center())
+                    .on_press(Message::CycleSpectrumAveraging)
+                    .width(Length::Fill),
+            )
+            .push(
+                button(self.label(self.readout_mode.label()).width(Length::Fill).center())
+                    .on_press(Message::CycleReadoutMode)
+                    .width(Length::Fill),
+            )
+            .push(segment_display(self.readout(0), theme, scale))
+            .push(segment_display(self.readout(1), theme, scale))
+    }
+
+    /// A control for every parameter, grouped into sections. This is the right column in the wide
+    /// layout.
+    fn control_section(&self) -> Column<'_, Message, Theme, Renderer> {
+        let scale = self.scale();
+        let mut column = Column::new()
+            .spacing(5.0 * scale)
+            .push(self.preset_browser())
+            .push(self.snapshot_controls())
+            .push(self.history_controls());
+        let num_bands = self.params.prismatine_params.num_bands.value() as usize;
+        for (idx, section) in self.sections.iter().enumerate() {
+            if !band_in_use(&section.name, "Band ", num_bands) {
+                continue;
+            }
+            let marker = if section.expanded { "-" } else { "+" };
+            column = column.push(
+                button(self.label(format!("{marker} {}", section.name)).width(Length::Fill))
+                    .on_press(Message::ToggleSection(idx))
+                    .width(Length::Fill),
+            );
+            if !section.expanded {
+                continue;
+            }
+
+            // In the knob layout the continuous parameters share rows, the rest keeps a full row
+            let mut knobs = Row::new().spacing(5.0 * scale);
+            let mut controls = Column::new().spacing(5.0 * scale);
+            for (id, param) in &section.params {
+                if !band_in_use(id, "crossover_", num_bands - 1) {
+                    continue;
+                }
+                match (self.control_layout(), param) {
+                    (ControlLayout::Knobs, ParamPtr::FloatParam(_) | ParamPtr::IntParam(_)) => {
+                        knobs = knobs.push(self.param_control(*param));
+                    }
+                    _ => controls = controls.push(self.param_control(*param)),
+                }
+            }
+            column = column.push(knobs.wrap()).push(controls);
+            if let Some(extras) = self.section_extras(&section.name) {
+                column = column.push(extras);
+            }
+        }
+
+        column
+    }
+
+    /// The widget for a single parameter, a toggle for booleans and enums and a slider or a knob
+    /// for everything else, depending on the [`ControlLayout`]. Parameters the morph sets get a
+    /// stand-in instead, which edits the stored end while the morph is at one.
+    fn param_control(&self, param: ParamPtr) -> Element<'_, Message, Theme, Renderer> {
+        let params = &self.params.prismatine_params;
+        let amount = params.morph.modulated_plain_value();
+        let morphed = params.morph_targets.read().unwrap().value(param, amount);
+        // SAFETY: The pointers come from the parameter map of the `PrismatineParams` this editor
+        //         holds on to, so they stay valid for as long as the editor exists
+        unsafe {
+            let continuous = matches!(param, ParamPtr::FloatParam(_) | ParamPtr::IntParam(_));
+            match (morphed, morph::end_at(amount)) {
+                (Some(MorphedValue::Value(normalized)), Some(b)) => {
+                    return self.morph_end_control(param, b, normalized, continuous);
+                }
+                (Some(MorphedValue::Value(normalized)), None) => {
+                    let value = param.normalized_value_to_string(normalized, true);
+                    return self.morphed_control(param.name(), value, continuous);
+                }
+                (Some(MorphedValue::Crossfade(a, b)), _) => {
+                    let value = format!(
+                        "{} to {}",
+                        param.normalized_value_to_string(a, true),
+                        param.normalized_value_to_string(b, true)
+                    );
+                    return self.morphed_control(param.name(), value, continuous);
+                }
+                (None, _) => (),
+            }
+            match param {
+                ParamPtr::BoolParam(p) => self.toggle((*p).name(), &*p).into(),
+                ParamPtr::EnumParam(p) => self.toggle((*p).name(), &*p).into(),
+                ParamPtr::FloatParam(p) => self.continuous_control((*p).name(), &*p),
+                ParamPtr::IntParam(p) => self.continuous_control((*p).name(), &*p),
+            }
+        }
+    }
+
+    /// Stands in for the control of a parameter the morph sets between its ends, showing the value
+    /// it's morphed to. Crossfaded discrete parameters show both ends. The button has nothing to do,
+    /// so it's drawn greyed out.
+    fn morphed_control(&self, label: &str, value: String, continuous: bool) -> Element<'_, Message, Theme, Renderer> {
+        let column = Column::new()
+            .push(self.label(label.to_owned()).width(Length::Fill).center())
+            .push(button(self.label(value).width(Length::Fill).center()).width(Length::Fill));
+        self.morph_stand_in(column, continuous)
+    }
+
+    /// Stands in for the control of a parameter the morph sets while the morph is at the A or B
+    /// end, editing the value stored for that end. The stored values aren't parameters, so they get
+    /// a plain slider, or a button that steps through the values like a [`ParamToggle`].
+    ///
+    /// # Safety
+    ///
+    /// `param` needs to point to one of this editor's parameters.
+    unsafe fn morph_end_control(
+        &self,
+        param: ParamPtr,
+        b: bool,
+        normalized: f32,
+        continuous: bool,
+    ) -> Element<'_, Message, Theme, Renderer> {
+        let value = self.label(param.normalized_value_to_string(normalized, true)).width(Length::Fill).center();
+        let column = Column::new().push(self.label(param.name().to_owned()).width(Length::Fill).center());
+        let column = match param.step_count() {
+            Some(steps) if !continuous => {
+                let current = (normalized * steps as f32).round() as usize;
+                let next = ((current + 1) % (steps + 1)) as f32 / steps as f32;
+                column.push(
+                    button(value)
+                        .style(button::secondary)
+                        .on_press(Message::SetMorphTarget(param, b, next))
+                        .width(Length::Fill),
+                )
+            }
+            _ => column
+                .push(
+                    slider(0.0..=1.0, normalized, move |normalized| Message::SetMorphTarget(param, b, normalized))
+                        .step(0.001),
+                )
+                .push(value),
+        };
+        self.morph_stand_in(column, continuous)
+    }
+
+    /// Sizes a morph stand-in like the control it replaces.
+    fn morph_stand_in<'a>(
+        &self,
+        column: Column<'a, Message, Theme, Renderer>,
+        continuous: bool,
+    ) -> Element<'a, Message, Theme, Renderer> {
+        if continuous && self.control_layout() == ControlLayout::Knobs {
+            column.width(KNOB_CELL_WIDTH * self.scale()).into()
+        } else {
+            column.into()
+        }
+    }
+
+    /// Widgets that belong to a section but aren't parameters.
+    fn section_extras(&self, section: &str) -> Option<Element<'_, Message, Theme, Renderer>> {
+        let params = &self.params.prismatine_params;
+        let scale = self.scale();
+        match section {
+            "Junction" => {
+                let mut column = Column::new().spacing(5.0 * scale);
+                if params.cpr_shape.value() == CprShape::Wavetable {
+                    column = column.push(
+                        canvas(WavetableCanvas { points: &params.cpr_wavetable, theme: self.editor_theme() })
+                            .width(Length::Fill)
+                            .height(80.0 * scale),
+                    );
+                }
+                Some(
+                    column
+                        .push(self.label("I-V characteristic").width(Length::Fill).center())
+                        .push(
+                            canvas(IvPlot { params, theme: self.editor_theme() })
+                                .width(Length::Fill)
+                                .height(120.0 * scale),
+                        )
+                        .into(),
+                )
+            }
+            "Multiband" => Some(self.band_meters().into()),
+            "Morph" => Some(self.morph_controls().into()),
+            _ => None,
+        }
+    }
+
+    /// The text for a channel's display in the second row, depending on the readout mode.
+    fn readout(&self, channel: usize) -> String {
+        match self.readout_mode {
+            ReadoutMode::Revolutions => {
+                let revolutions = self.params.revolutions[channel].load(Ordering::Relaxed);
+                // Only four characters fit on the display, larger counts stop at the limits
+                format!("{:>4}", revolutions.clamp(-999, 9999))
+            }
+            ReadoutMode::Voltage => {
+                let voltage = self.params.voltage[channel].load(Ordering::Relaxed) / 1000.0;
+                format!("{:>5.1}", voltage.clamp(-99.9, 999.9))
+            }
+        }
+    }
+
+    fn continuous_control<'a, P: Param>(&self, label: &'a str, param: &'a P) -> Element<'a, Message, Theme, Renderer> {
+        match self.control_layout() {
+            ControlLayout::Sliders => self.slider(label, param).into(),
+            ControlLayout::Knobs => self.knob(label, param),
+        }
+    }
+
+    fn slider<'a, P: Param>(&self, label: &'a str, param: &'a P) -> Column<'a, Message, Theme, Renderer> {
+        Column::new()
+            .push(self.label(label).width(Length::Fill).center())
+            .push(ParamSlider::new(param).text_size(TEXT_SIZE * self.scale()).map(Message::ParamUpdate))
+    }
+
+    /// A knob with the value in a tooltip, and a ring showing how far the host modulates the
+    /// parameter away from its knob position.
+    fn knob<'a, P: Param>(&self, label: &'a str, param: &'a P) -> Element<'a, Message, Theme, Renderer> {
+        let scale = self.scale();
+        let ptr = param.as_ptr();
+        let size = KNOB_SIZE * scale;
+        let knob = Knob::new(
+            NormalParam {
+                value: Normal::from_clipped(param.unmodulated_normalized_value()),
+                default: Normal::from_clipped(param.default_normalized_value()),
+            },
+            move |normal| Message::ParamUpdate(ParamMessage::SetParameterNormalized(ptr, normal.as_f32())),
+        )
+        .on_grab(move || Some(Message::ParamUpdate(ParamMessage::BeginSetParameter(ptr))))
+        .on_release(move || Some(Message::ParamUpdate(ParamMessage::EndSetParameter(ptr))))
+        .size(Length::Fixed(size));
+        let ring = canvas(ModulationRing {
+            from: param.unmodulated_normalized_value(),
+            to: param.modulated_normalized_value(),
+            theme: self.editor_theme(),
+        })
+        .width(size + 8.0 * scale)
+        .height(size + 8.0 * scale);
+        let value = param.normalized_value_to_string(param.modulated_normalized_value(), true);
+
+        Column::new()
+            .width(KNOB_CELL_WIDTH * scale)
+            .align_x(Alignment::Center)
+            .push(
+                tooltip(
+                    Stack::new().push(ring).push(container(knob).center(size + 8.0 * scale)),
+                    container(self.label(value)).padding(3.0 * scale).style(container::rounded_box),
+                    tooltip::Position::Bottom,
+                ),
+            )
+            .push(Text::new(label).size(TEXT_SIZE * 0.85 * scale).width(Length::Fill).center())
             .into()
     }
+
+    fn toggle<'a, P: Param>(&self, label: &'a str, param: &'a P) -> Column<'a, Message, Theme, Renderer> {
+        Column::new()
+            .push(self.label(label).width(Length::Fill).center())
+            .push(ParamToggle::new(param).text_size(TEXT_SIZE * self.scale()).map(Message::ParamUpdate))
+    }
+
+    /// Buttons to store both ends of the morph, and whether the morph is active. The ends can only
+    /// be stored and edited while the morph is at one of them.
+    fn morph_controls(&self) -> Column<'_, Message, Theme, Renderer> {
+        let scale = self.scale();
+        let params = &self.params.prismatine_params;
+        let targets = params.morph_targets.read().unwrap();
+        let end = morph::end_at(params.morph.modulated_plain_value());
+        let can_store = end.is_some() || !targets.is_morphing();
+        let store_button = |label: &'static str, stored: bool, b: bool| {
+            button(self.label(label).width(Length::Fill).center())
+                .style(if stored { button::primary } else { button::secondary })
+                .on_press_maybe(can_store.then_some(Message::StoreMorphTarget(b)))
+                .width(Length::Fill)
+        };
+        let status = match end {
+            _ if !targets.is_morphing() => "store two different A and B settings to morph between them",
+            Some(false) => "at A, the controls the morph sets edit A",
+            Some(true) => "at B, the controls the morph sets edit B",
+            None => "morphing, move to A or B to store or edit them",
+        };
+
+        Column::new().spacing(5.0 * scale)
+            .push(
+                Row::new().spacing(5.0 * scale)
+                    .push(store_button("store A", targets.a.is_some(), false))
+                    .push(store_button("store B", targets.b.is_some(), true))
+                    .push(button(self.label("clear")).on_press(Message::ClearMorphTargets)),
+            )
+            .push(self.label(status).width(Length::Fill).center())
+    }
+
+    /// The peak level of every active band.
+    fn band_meters(&self) -> Column<'_, Message, Theme, Renderer> {
+        let num_bands = self.params.prismatine_params.num_bands.value() as usize;
+        let scale = self.scale();
+        let mut column = Column::new().spacing(5.0 * scale);
+        for band in 0..num_bands {
+            let peak_db = gain_to_db(self.params.band_meters[band].load(std::sync::atomic::Ordering::Relaxed));
+            column = column.push(
+                Row::new().spacing(5.0 * scale)
+                    .push(self.label(format!("{}", band + 1)))
+                    .push(progress_bar(-60.0..=0.0, peak_db.max(-60.0)).height(10.0 * scale)),
+            );
+        }
+
+        column
+    }
+}
+
+/// Whether `name` belongs to a band or crossover that's used with the current number of bands,
+/// going by the number after `prefix`. Anything that doesn't start with `prefix` and a number is
+/// always used.
+fn band_in_use(name: &str, prefix: &str, count: usize) -> bool {
+    match name.strip_prefix(prefix).and_then(|number| number.parse::<usize>().ok()) {
+        Some(number) => number <= count,
+        None => true,
+    }
+}
+
+/// Ctrl+Z (Cmd+Z on macOS) undoes, and Ctrl+Shift+Z or Ctrl+Y redoes.
+fn undo_shortcut(key: keyboard::Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    if !modifiers.command() {
+        return None;
+    }
+
+    match key.as_ref() {
+        // The key comes in upper case while Shift or Caps Lock is held
+        keyboard::Key::Character("z" | "Z") if modifiers.shift() => Some(Message::Redo),
+        keyboard::Key::Character("z" | "Z") => Some(Message::Undo),
+        keyboard::Key::Character("y" | "Y") => Some(Message::Redo),
+        _ => None,
+    }
+}
+
+fn segment_display<'a>(text: String, theme: EditorTheme, scale: f32) -> Element<'a, Message, Theme, Renderer> {
+    canvas(SevenSegmentCanvas::new(
+        seven_segment_iced::glyph::string_with_decimals_to_segment(text),
+        4,
+        SevenSegmentStyle {
+            background_color: theme.display_background(),
+            segment_color: theme.display_foreground(),
+            off_color: None, //Color or inactive segments
+            margin_frac: 1.0 / 15.0,
+            aspect_ratio: 6.9,
+            line_margin_frac: 1.0 / 30.0,
+            dot_size_frac: 1.0 / 15.0,
+        },
+    ))
+    .width(Length::Fill)
+    .height(SEGMENT_DISPLAY_HEIGHT * scale)
+    .into()
+}
+
+/// Peak and RMS bars for a [`LevelMeter`], from -60 to 0 dBFS.
+fn level_meter<'a>(label: Text<'a, Theme, Renderer>, meter: &LevelMeter, scale: f32) -> Element<'a, Message, Theme, Renderer> {
+    let peak_db = gain_to_db(meter.peak.load(Ordering::Relaxed)).max(-60.0);
+    let rms_db = gain_to_db(meter.rms.load(Ordering::Relaxed)).max(-60.0);
+    Row::new()
+        .spacing(5.0 * scale)
+        .push(label.width(25.0 * scale))
+        .push(
+            Column::new()
+                .spacing(2.0 * scale)
+                .push(progress_bar(-60.0..=0.0, peak_db).height(6.0 * scale))
+                .push(progress_bar(-60.0..=0.0, rms_db).height(6.0 * scale)),
+        )
+        .push(Text::new(format!("{rms_db:.0}")).size(TEXT_SIZE * scale).width(25.0 * scale))
+        .into()
+}
+
+/// A handle in the bottom right corner that resizes the window when dragged.
+struct ResizeGrip {
+    theme: EditorTheme,
+}
+
+impl canvas::Program<Message, Theme, Renderer> for ResizeGrip {
+    /// The cursor position the window was last resized for while dragging, in window coordinates.
+    /// The window's top left corner stays put, so the difference is how much the window needs to
+    /// grow to follow the cursor.
+    type State = Option<Point>;
+
+    fn update(
+        &self,
+        last_position: &mut Option<Point>,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (canvas::event::Status, Option<Message>) {
+        match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) if cursor.is_over(bounds) => {
+                *last_position = cursor.position();
+                (canvas::event::Status::Captured, None)
+            }
+            canvas::Event::Mouse(mouse::Event::CursorMoved { position }) => match last_position {
+                Some(last) => {
+                    let (dx, dy) = (position.x - last.x, position.y - last.y);
+                    *last = position;
+                    (canvas::event::Status::Captured, Some(Message::ResizeWindowBy(dx, dy)))
+                }
+                None => (canvas::event::Status::Ignored, None),
+            },
+            canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) if last_position.take().is_some() => {
+                (canvas::event::Status::Captured, None)
+            }
+            _ => (canvas::event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        _last_position: &Option<Point>,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let stroke = canvas::Stroke::default()
+            .with_color(self.theme.display_foreground())
+            .with_width(1.0);
+        let (width, height) = (bounds.width, bounds.height);
+        for fraction in [0.25, 0.5, 0.75] {
+            frame.stroke(
+                &canvas::Path::line(
+                    Point::new(width * fraction, height),
+                    Point::new(width, height * fraction),
+                ),
+                stroke.clone(),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        drag_start: &Option<Point>,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        if drag_start.is_some() || cursor.is_over(bounds) {
+            mouse::Interaction::ResizingDiagonallyDown
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+}
+
+/// The track around a knob, with the range between the knob position and the modulated value
+/// highlighted.
+struct ModulationRing {
+    /// The unmodulated normalized value.
+    from: f32,
+    /// The modulated normalized value.
+    to: f32,
+    theme: EditorTheme,
+}
+
+impl ModulationRing {
+    /// The angle of the lowest value, clockwise from the positive x axis.
+    const START_ANGLE: f32 = 0.75 * f32::consts::PI;
+    /// The angle the knob sweeps through from the lowest to the highest value.
+    const SWEEP: f32 = 1.5 * f32::consts::PI;
+
+    fn arc(center: Point, radius: f32, from: f32, to: f32) -> canvas::Path {
+        canvas::Path::new(|builder| {
+            builder.arc(canvas::path::Arc {
+                center,
+                radius,
+                start_angle: Radians(Self::START_ANGLE + from.min(to) * Self::SWEEP),
+                end_angle: Radians(Self::START_ANGLE + from.max(to) * Self::SWEEP),
+            })
+        })
+    }
+}
+
+impl canvas::Program<Message, Theme, Renderer> for ModulationRing {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let foreground = self.theme.display_foreground();
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        let center = frame.center();
+        let radius = bounds.width.min(bounds.height) / 2.0 - 2.0;
+
+        frame.stroke(
+            &Self::arc(center, radius, 0.0, 1.0),
+            canvas::Stroke::default().with_color(Color { a: 0.2, ..foreground }).with_width(2.0),
+        );
+        if (self.to - self.from).abs() > f32::EPSILON {
+            frame.stroke(
+                &Self::arc(center, radius, self.from, self.to),
+                canvas::Stroke::default().with_color(foreground).with_width(3.0),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Overlays the input and the wet output, triggered on a rising zero crossing of the input.
+struct Oscilloscope<'a> {
+    metering: &'a Metering,
+    theme: EditorTheme,
+}
+
+impl Oscilloscope<'_> {
+    /// The number of samples shown after the trigger point.
+    const VISIBLE_SAMPLES: usize = SCOPE_SIZE / 4;
+}
+
+impl canvas::Program<Message, Theme, Renderer> for Oscilloscope<'_> {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let background = self.theme.display_background();
+        let foreground = self.theme.display_foreground();
+
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), background);
+
+        let mut input = [0.0; SCOPE_SIZE];
+        let mut output = [0.0; SCOPE_SIZE];
+        let delay = self.metering.scope_delay.load(Ordering::Relaxed);
+        self.metering.input_scope.read_delayed(&mut input, delay);
+        self.metering.output_scope.read_latest(&mut output);
+
+        // Search backwards so the most recent trigger that still leaves a full view is used, or
+        // free run if there is none
+        let last_start = SCOPE_SIZE - Self::VISIBLE_SAMPLES;
+        let trigger = (1..=last_start)
+            .rev()
+            .find(|&i| input[i - 1] <= 0.0 && input[i] > 0.0)
+            .unwrap_or(last_start);
+
+        let to_point = |i: usize, value: f32| {
+            Point::new(
+                i as f32 / (Self::VISIBLE_SAMPLES - 1) as f32 * bounds.width,
+                (1.0 - value.clamp(-1.0, 1.0)) * 0.5 * bounds.height,
+            )
+        };
+        for (samples, color) in [
+            (&input, Color { a: 0.4, ..foreground }),
+            (&output, foreground),
+        ] {
+            let path = canvas::Path::new(|builder| {
+                let visible = &samples[trigger..trigger + Self::VISIBLE_SAMPLES];
+                builder.move_to(to_point(0, visible[0]));
+                for (i, value) in visible.iter().enumerate().skip(1) {
+                    builder.line_to(to_point(i, *value));
+                }
+            });
+            frame.stroke(&path, canvas::Stroke::default().with_color(color).with_width(1.0));
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// The input and output spectra overlaid on a log frequency axis from 20 Hz to 20 kHz.
+struct SpectrumView<'a> {
+    metering: &'a Metering,
+    averaging: SpectrumAveraging,
+    theme: EditorTheme,
+}
+
+impl SpectrumView<'_> {
+    const MIN_FREQUENCY: f32 = 20.0;
+    const MAX_FREQUENCY: f32 = 20000.0;
+}
+
+impl canvas::Program<Message, Theme, Renderer> for SpectrumView<'_> {
+    /// The analyzer lives in the widget state so the averaging carries over between frames.
+    type State = RefCell<SpectrumAnalyzer>;
+
+    fn draw(
+        &self,
+        analyzer: &RefCell<SpectrumAnalyzer>,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let background = self.theme.display_background();
+        let foreground = self.theme.display_foreground();
+
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), background);
+
+        let sample_rate = self.metering.sample_rate.load(Ordering::Relaxed);
+        if sample_rate <= 0.0 {
+            return vec![frame.into_geometry()];
+        }
+        let mut analyzer = analyzer.borrow_mut();
+        analyzer.update(self.metering, self.averaging);
+
+        let max_frequency = Self::MAX_FREQUENCY.min(sample_rate / 2.0);
+        let octaves = (max_frequency / Self::MIN_FREQUENCY).log2();
+        let num_points = bounds.width.max(2.0) as usize;
+        for (spectrum, color) in [(0, Color { a: 0.4, ..foreground }), (1, foreground)] {
+            let path = canvas::Path::new(|builder| {
+                for i in 0..num_points {
+                    let x = i as f32 / (num_points - 1) as f32;
+                    let frequency = Self::MIN_FREQUENCY * 2.0f32.powf(x * octaves);
+                    let db = analyzer.level_at(spectrum, frequency, sample_rate);
+                    let point = Point::new(x * bounds.width, db / SPECTRUM_FLOOR_DB * bounds.height);
+                    if i == 0 {
+                        builder.move_to(point);
+                    } else {
+                        builder.line_to(point);
+                    }
+                }
+            });
+            frame.stroke(&path, canvas::Stroke::default().with_color(color).with_width(1.0));
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Draws each channel's phase as a rotating vector on the unit circle, with a fading trail of the
+/// recent phase values.
+struct PhaseCircle<'a> {
+    phase: &'a [AtomicF32; 2],
+    history: &'a [AtomicRingBuffer<PHASE_HISTORY_SIZE>; 2],
+    theme: EditorTheme,
+}
+
+impl PhaseCircle<'_> {
+    /// The number of history values drawn as the trail.
+    const TRAIL_LENGTH: usize = 64;
+    /// The longest straight line used to draw the trail's arcs, in radians.
+    const ARC_STEP: f32 = 0.1;
+}
+
+impl canvas::Program<Message, Theme, Renderer> for PhaseCircle<'_> {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let background = self.theme.display_background();
+        let foreground = self.theme.display_foreground();
+
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), background);
+
+        let radius = (bounds.width / 4.0).min(bounds.height / 2.0) * 0.85;
+        for (channel, (phase, history)) in self.phase.iter().zip(self.history).enumerate() {
+            let center = Point::new(bounds.width * (0.25 + 0.5 * channel as f32), bounds.height / 2.0);
+            let on_circle = |phase: f32| Point::new(center.x + radius * phase.cos(), center.y - radius * phase.sin());
+
+            frame.stroke(
+                &canvas::Path::circle(center, radius),
+                canvas::Stroke::default()
+                    .with_color(Color { a: 0.3, ..foreground })
+                    .with_width(1.0),
+            );
+
+            let mut trail = [0.0; Self::TRAIL_LENGTH];
+            let count = history.read_latest(&mut trail);
+            let trail = &trail[Self::TRAIL_LENGTH - count..];
+            for (i, segment) in trail.windows(2).enumerate() {
+                let alpha = (i + 1) as f32 / trail.len() as f32;
+                // Follow the circle the short way around instead of cutting across it
+                let delta = (segment[1] - segment[0] + f32::consts::PI).rem_euclid(f32::consts::TAU) - f32::consts::PI;
+                let steps = ((delta.abs() / Self::ARC_STEP).ceil() as usize).max(1);
+                let arc = canvas::Path::new(|builder| {
+                    builder.move_to(on_circle(segment[0]));
+                    for step in 1..=steps {
+                        builder.line_to(on_circle(segment[0] + delta * step as f32 / steps as f32));
+                    }
+                });
+                frame.stroke(
+                    &arc,
+                    canvas::Stroke::default()
+                        .with_color(Color { a: alpha * 0.8, ..foreground })
+                        .with_width(2.0),
+                );
+            }
+
+            frame.stroke(
+                &canvas::Path::line(center, on_circle(phase.load(Ordering::Relaxed))),
+                canvas::Stroke::default().with_color(foreground).with_width(2.0),
+            );
+        }
+
+        vec![frame.into_geometry()]
+    }
+}
+
+/// Everything the I-V curve depends on, so it's only recomputed when one of these changes.
+#[derive(Clone, PartialEq)]
+struct IvCurveKey {
+    phase_gain: f32,
+    critical_current: f32,
+    invert_phase: bool,
+    cpr_shape: CprShape,
+    cpr_amount: f32,
+    quantize_steps: u32,
+    quantize_hysteresis: f32,
+    phase_offset: f32,
+    wavetable: Vec<f32>,
+}
+
+/// The last sweep and its drawing. The geometry is only redrawn when the curve, the theme or the
+/// plot's size changes.
+#[derive(Default)]
+struct IvCurveCache {
+    key: Option<IvCurveKey>,
+    theme: Option<EditorTheme>,
+    curve: Vec<(f32, f32)>,
+    geometry: canvas::Cache<Renderer>,
+}
+
+/// The current-voltage characteristic of the first band's junction at the current settings, with
+/// the voltage on the horizontal axis and the bias current on the vertical axis.
+struct IvPlot<'a> {
+    params: &'a PrismatineParams,
+    theme: EditorTheme,
+}
+
+impl IvPlot<'_> {
+    /// Whether `key` describes the current settings. Compares the wavetable in place so it's only
+    /// copied when the curve needs to be recomputed.
+    fn is_current(&self, key: &IvCurveKey) -> bool {
+        let params = self.params;
+        key.phase_gain == params.phase_gain.value()
+            && key.critical_current == params.I_c.value()
+            && key.invert_phase == params.invert_phase.value()
+            && key.cpr_shape == params.cpr_shape.value()
+            && key.cpr_amount == params.cpr_amount.value()
+            && key.quantize_steps == params.phase_steps.value() as u32
+            && key.quantize_hysteresis == params.phase_hysteresis.value()
+            && key.phase_offset == params.phase_offset.value().to_radians()
+            && key.wavetable == *params.cpr_wavetable.read().unwrap()
+    }
+
+    fn key(&self) -> IvCurveKey {
+        let params = self.params;
+        IvCurveKey {
+            phase_gain: params.phase_gain.value(),
+            critical_current: params.I_c.value(),
+            invert_phase: params.invert_phase.value(),
+            cpr_shape: params.cpr_shape.value(),
+            cpr_amount: params.cpr_amount.value(),
+            quantize_steps: params.phase_steps.value() as u32,
+            quantize_hysteresis: params.phase_hysteresis.value(),
+            phase_offset: params.phase_offset.value().to_radians(),
+            wavetable: params.cpr_wavetable.read().unwrap().clone(),
+        }
+    }
+}
+
+impl canvas::Program<Message, Theme, Renderer> for IvPlot<'_> {
+    type State = RefCell<IvCurveCache>;
+
+    fn draw(
+        &self,
+        cache: &RefCell<IvCurveCache>,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let mut cache = cache.borrow_mut();
+        if !cache.key.as_ref().is_some_and(|key| self.is_current(key)) {
+            let key = self.key();
+            let mut cpr = CurrentPhaseRelation::default();
+            cpr.set_wavetable(&key.wavetable);
+            cpr.update(key.cpr_shape, key.cpr_amount);
+            cache.curve = iv_curve(&JunctionSettings {
+                phase_gain: key.phase_gain,
+                critical_current: key.critical_current,
+                invert_phase: key.invert_phase,
+                quantize_steps: key.quantize_steps,
+                quantize_hysteresis: key.quantize_hysteresis,
+                phase_offset: key.phase_offset,
+                cpr: &cpr,
+            });
+            cache.key = Some(key);
+            cache.geometry.clear();
+        }
+        if cache.theme != Some(self.theme) {
+            cache.theme = Some(self.theme);
+            cache.geometry.clear();
+        }
+
+        let background = self.theme.display_background();
+        let foreground = self.theme.display_foreground();
+        let critical_current = cache.key.as_ref().map_or(1.0, |key| key.critical_current);
+        let curve = &cache.curve;
+        let geometry = cache.geometry.draw(renderer, bounds.size(), |frame| {
+            frame.fill_rectangle(Point::ORIGIN, bounds.size(), background);
+
+            let max_current = critical_current * IV_CURVE_RANGE;
+            let max_voltage = curve
+                .iter()
+                .fold(f32::EPSILON, |max, (voltage, _)| max.max(voltage.abs()));
+            let to_point = |voltage: f32, current: f32| {
+                Point::new(
+                    (0.5 + 0.5 * voltage / max_voltage) * bounds.width,
+                    (0.5 - 0.5 * current / max_current) * bounds.height,
+                )
+            };
+
+            let axes = canvas::Stroke::default()
+                .with_color(Color { a: 0.3, ..foreground })
+                .with_width(1.0);
+            frame.stroke(&canvas::Path::line(to_point(-max_voltage, 0.0), to_point(max_voltage, 0.0)), axes.clone());
+            frame.stroke(&canvas::Path::line(to_point(0.0, -max_current), to_point(0.0, max_current)), axes.clone());
+            // The critical current, where the knee should be
+            for current in [-critical_current, critical_current] {
+                frame.stroke(&canvas::Path::line(to_point(-max_voltage, current), to_point(max_voltage, current)), axes.clone());
+            }
+
+            let path = canvas::Path::new(|builder| {
+                for (i, (voltage, current)) in curve.iter().enumerate() {
+                    let point = to_point(*voltage, *current);
+                    if i == 0 {
+                        builder.move_to(point);
+                    } else {
+                        builder.line_to(point);
+                    }
+                }
+            });
+            frame.stroke(&path, canvas::Stroke::default().with_color(foreground).with_width(1.5));
+        });
+
+        vec![geometry]
+    }
+}
+
+/// Draws the user wavetable and lets the user paint new points with the mouse.
+struct WavetableCanvas<'a> {
+    points: &'a RwLock<Vec<f32>>,
+    theme: EditorTheme,
+}
+
+impl canvas::Program<Message, Theme, Renderer> for WavetableCanvas<'_> {
+    /// Whether the left mouse button is held down.
+    type State = bool;
+
+    fn update(
+        &self,
+        drawing: &mut bool,
+        event: canvas::Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (canvas::event::Status, Option<Message>) {
+        match event {
+            canvas::Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) if cursor.is_over(bounds) => {
+                *drawing = true;
+            }
+            canvas::Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) => {
+                *drawing = false;
+                return (canvas::event::Status::Ignored, None);
+            }
+            canvas::Event::Mouse(mouse::Event::CursorMoved { .. }) if *drawing => (),
+            _ => return (canvas::event::Status::Ignored, None),
+        }
+
+        match cursor.position_in(bounds) {
+            Some(position) => {
+                let idx = ((position.x / bounds.width) * USER_WAVETABLE_SIZE as f32) as usize;
+                let value = 1.0 - 2.0 * position.y / bounds.height;
+                (
+                    canvas::event::Status::Captured,
+                    Some(Message::DrawWavetable(idx.min(USER_WAVETABLE_SIZE - 1), value.clamp(-1.0, 1.0))),
+                )
+            }
+            None => (canvas::event::Status::Captured, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        _drawing: &bool,
+        renderer: &Renderer,
+        _theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<canvas::Geometry<Renderer>> {
+        let mut frame = canvas::Frame::new(renderer, bounds.size());
+        frame.fill_rectangle(Point::ORIGIN, bounds.size(), self.theme.display_background());
+
+        let points = self.points.read().unwrap();
+        let step = bounds.width / points.len().max(1) as f32;
+        let to_y = |value: f32| (1.0 - value) * 0.5 * bounds.height;
+        let path = canvas::Path::new(|builder| {
+            for (i, value) in points.iter().enumerate() {
+                let point = Point::new((i as f32 + 0.5) * step, to_y(*value));
+                if i == 0 {
+                    builder.move_to(point);
+                } else {
+                    builder.line_to(point);
+                }
+            }
+        });
+        frame.stroke(
+            &path,
+            canvas::Stroke::default()
+                .with_color(self.theme.display_foreground())
+                .with_width(1.5),
+        );
+
+        vec![frame.into_geometry()]
+    }
 }
@@ -0,0 +1,48 @@
+use crate::post_filter::{Biquad, BiquadCoefficients};
+
+/// Matched pre- and de-emphasis shelves around the junction. The pre-emphasis decides which
+/// frequencies drive the phase the hardest, the de-emphasis undoes the tonal change afterwards.
+pub struct Emphasis {
+    pre: [Biquad; 2],
+    de: [Biquad; 2],
+    /// Frequency and amount the coefficients were last computed for.
+    settings: Option<(f32, f32)>,
+}
+
+impl Default for Emphasis {
+    fn default() -> Self {
+        Self {
+            pre: [Biquad::default(); 2],
+            de: [Biquad::default(); 2],
+            settings: None,
+        }
+    }
+}
+
+impl Emphasis {
+    /// Recompute the shelves if the frequency or amount (in dB) changed. The de-emphasis shelf
+    /// uses the negated gain, which makes it the exact inverse of the pre-emphasis shelf.
+    pub fn update(&mut self, sample_rate: f32, frequency: f32, amount: f32) {
+        if self.settings == Some((frequency, amount)) {
+            return;
+        }
+        self.settings = Some((frequency, amount));
+
+        let pre = BiquadCoefficients::high_shelf(sample_rate, frequency, amount);
+        let de = BiquadCoefficients::high_shelf(sample_rate, frequency, -amount);
+        self.pre.iter_mut().for_each(|f| f.set_coefficients(pre));
+        self.de.iter_mut().for_each(|f| f.set_coefficients(de));
+    }
+
+    pub fn pre(&mut self, channel: usize, sample: f32) -> f32 {
+        self.pre[channel].process(sample)
+    }
+
+    pub fn de(&mut self, channel: usize, sample: f32) -> f32 {
+        self.de[channel].process(sample)
+    }
+
+    pub fn reset(&mut self) {
+        self.pre.iter_mut().chain(self.de.iter_mut()).for_each(Biquad::reset);
+    }
+}
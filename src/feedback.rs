@@ -0,0 +1,84 @@
+/// The longest feedback delay the delay line is allocated for, in milliseconds.
+pub const MAX_FEEDBACK_DELAY_MS: f32 = 50.0;
+/// The pole of the DC blocker in the feedback path.
+const DC_BLOCKER_POLE: f32 = 0.995;
+
+/// Feeds the junction's output back into its drive through a short delay. The feedback is DC
+/// blocked and soft clipped so it can't run away.
+pub struct Feedback {
+    delay_lines: Vec<Vec<f32>>,
+    write_pos: usize,
+    /// Last input and output of each channel's DC blocker.
+    dc_blocker: Vec<(f32, f32)>,
+}
+
+impl Feedback {
+    pub fn new(channels: usize, sample_rate: f32) -> Self {
+        // One extra sample for the interpolation
+        let max_delay = (sample_rate * MAX_FEEDBACK_DELAY_MS / 1000.0).ceil() as usize + 2;
+        Self {
+            delay_lines: vec![vec![0.0; max_delay]; channels],
+            write_pos: 0,
+            dc_blocker: vec![(0.0, 0.0); channels],
+        }
+    }
+
+    /// Read the feedback signal from `delay` samples ago, before this sample's output is written.
+    /// Delays shorter than one sample feed back the previous output.
+    pub fn read(&mut self, channel: usize, delay: f32) -> f32 {
+        let line = &self.delay_lines[channel];
+        let len = line.len();
+        let delay = delay.max(1.0).min((len - 2) as f32);
+        let whole = delay.floor() as usize;
+        let frac = delay - whole as f32;
+        let a = line[(self.write_pos + len - whole) % len];
+        let b = line[(self.write_pos + len - whole - 1) % len];
+        let delayed = a + (b - a) * frac;
+
+        let (prev_in, prev_out) = &mut self.dc_blocker[channel];
+        let blocked = delayed - *prev_in + DC_BLOCKER_POLE * *prev_out;
+        *prev_in = delayed;
+        *prev_out = blocked;
+
+        blocked.tanh()
+    }
+
+    /// Write the current output of `channel`. Call [`Feedback::advance`] once all channels have
+    /// been written.
+    pub fn write(&mut self, channel: usize, sample: f32) {
+        self.delay_lines[channel][self.write_pos] = if sample.is_finite() { sample } else { 0.0 };
+    }
+
+    pub fn advance(&mut self) {
+        if let Some(line) = self.delay_lines.first() {
+            self.write_pos = (self.write_pos + 1) % line.len();
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.delay_lines.iter_mut().for_each(|line| line.fill(0.0));
+        self.dc_blocker.iter_mut().for_each(|state| *state = (0.0, 0.0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn impulse_returns_after_delay() {
+        for delay in [1, 2, 5, 48] {
+            let mut feedback = Feedback::new(1, 48000.0);
+            let mut arrival = None;
+            for t in 0..100 {
+                let out = feedback.read(0, delay as f32);
+                if arrival.is_none() && out.abs() > 0.1 {
+                    arrival = Some(t);
+                }
+                feedback.write(0, if t == 0 { 1.0 } else { 0.0 });
+                feedback.advance();
+            }
+            assert_eq!(arrival, Some(delay), "delay of {delay} samples");
+        }
+    }
+}
@@ -1,5 +1,92 @@
 
 use nih_plug::buffer::{Buffer, SamplesIter};
+use realfft::{num_complex::Complex32, ComplexToReal, RealToComplex};
+
+/// The FFT plans for the current block size and the scratch space they share, passed around as one
+/// to the spectral processors.
+pub struct FftPlans<'a>
+{
+    pub r2c: &'a dyn RealToComplex<f32>,
+    pub c2r: &'a dyn ComplexToReal<f32>,
+    pub scratch: &'a mut [Complex32],
+}
+
+impl FftPlans<'_>
+{
+    pub fn forward(&mut self, input: &mut [f32], spectrum: &mut [Complex32])
+    {
+        self.r2c.process_with_scratch(input, spectrum, self.scratch).unwrap();
+    }
+
+    /// The inverse real FFT needs the DC and Nyquist bins to be purely real, so their imaginary
+    /// parts are dropped first.
+    pub fn inverse(&mut self, spectrum: &mut [Complex32], output: &mut [f32])
+    {
+        let num_bins = spectrum.len();
+        spectrum[0].im = 0.0;
+        spectrum[num_bins - 1].im = 0.0;
+        self.c2r.process_with_scratch(spectrum, output, self.scratch).unwrap();
+    }
+}
+
+/// Overlap-add accumulators for blocks processed as frames that overlap by half a block, one block
+/// and one hop long per channel. The first block's worth is handed out, the last hop carries over
+/// into the next block.
+pub struct OverlapAdd
+{
+    buffers: Vec<Vec<f32>>,
+}
+
+impl OverlapAdd
+{
+    pub fn new(channels: usize, max_window_size: usize) -> Self
+    {
+        Self {
+            buffers: vec![vec![0.0; max_window_size + max_window_size / 2]; channels],
+        }
+    }
+
+    /// Start accumulating a `window_size` sample block by shifting the tail of the last block to
+    /// the front.
+    pub fn start_block(&mut self, channel: usize, window_size: usize)
+    {
+        let hop = window_size / 2;
+        let buffer = &mut self.buffers[channel][..window_size + hop];
+        buffer.copy_within(window_size.., 0);
+        buffer[hop..].fill(0.0);
+    }
+
+    /// Add `samples` to the block, starting `offset` samples in.
+    pub fn add(&mut self, channel: usize, offset: usize, samples: &[f32])
+    {
+        for (acc, sample) in self.buffers[channel][offset..].iter_mut().zip(samples)
+        {
+            *acc += sample;
+        }
+    }
+
+    /// Copy the finished block to `block`.
+    pub fn finish_block(&self, channel: usize, block: &mut [f32])
+    {
+        block.copy_from_slice(&self.buffers[channel][..block.len()]);
+    }
+
+    /// The part of the last `window_size` sample block that carries over into the next one.
+    pub fn tail(&self, channel: usize, window_size: usize) -> &[f32]
+    {
+        &self.buffers[channel][window_size..window_size + window_size / 2]
+    }
+
+    pub fn clear(&mut self, channel: usize)
+    {
+        self.buffers[channel].fill(0.0);
+    }
+
+    pub fn reset(&mut self)
+    {
+        self.buffers.iter_mut().for_each(|buffer| buffer.fill(0.0));
+    }
+}
 
 pub struct FFTHelper
 {
@@ -17,6 +104,8 @@ pub struct FFTHelper
 impl FFTHelper
 {
 
+    /// Allocates buffers for blocks of up to `block_size` samples. The block size can later be
+    /// lowered with [`FFTHelper::set_block_size`] without reallocating.
     pub fn new(channels: usize, block_size: usize) -> Self
     {
         Self { input_buffer: vec![vec![vec![0.0; block_size]; channels];2],
@@ -26,6 +115,19 @@ impl FFTHelper
         out_buffer_idx: 0,
         sample_cnt: 0 }
     }
+    /// Change the block size without allocating. `block_size` may not be larger than the size
+    /// passed to [`FFTHelper::new`]. This clears the buffers, so the output will be silent for one
+    /// block.
+    pub fn set_block_size(&mut self, block_size: usize)
+    {
+        assert!(block_size <= self.output_buffer.first().map_or(0, |v| v.len()),
+            "block size exceeds the allocated capacity");
+        self.block_size = block_size;
+        self.buffer_idx = 0;
+        self.out_buffer_idx = 0;
+        self.sample_cnt = 0;
+        self.reset();
+    }
     pub fn reset(&mut self)
     {
         //Clear buffers
@@ -52,7 +154,7 @@ impl FFTHelper
         let samples_to_process: usize =buf.samples();
         //Fill A buffer
         let mut buf_iter = buf.iter_samples();
-        let buffer_idx_before_process = self.buffer_idx;
+        let block_size = self.block_size;
         while samples_processed < samples_to_process
         {
             if self.sample_cnt >= self.block_size
@@ -62,7 +164,7 @@ impl FFTHelper
                 self.sample_cnt = 0;
                 for (id, ch_buff) in self.input_buffer[self.buffer_idx^0b1].iter_mut().enumerate()
                 {
-                    callback(id, ch_buff);
+                    callback(id, &mut ch_buff[..block_size]);
                 }
             }
            if let Some(samples_iter) = buf_iter.next()
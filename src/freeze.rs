@@ -0,0 +1,277 @@
+use std::f32::consts::TAU;
+
+use nih_plug::prelude::Enum;
+use realfft::num_complex::Complex32;
+
+use crate::fft_filter::{FftPlans, OverlapAdd};
+use crate::util::XorShift32;
+
+/// How the phases of a frozen spectrum evolve during resynthesis.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FreezePhaseMode {
+    /// Every frame gets new random phases. This gives a smeared, noisy drone.
+    #[name = "Random"]
+    Random,
+    /// Every bin's phase advances at the bin's center frequency. This gives a stable, tonal drone.
+    #[name = "Advancing"]
+    Advancing,
+}
+
+/// Fill `window` with the Hann window used for resynthesis. This overwrites the old contents, so
+/// it can be called again whenever the FFT size changes.
+pub fn resynthesis_window(window: &mut [f32])
+{
+    window.fill(1.0);
+    nih_plug::util::window::hann_in_place(window);
+}
+
+/// Where a channel is in the freeze.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelState
+{
+    /// Passing the input through.
+    Live,
+    /// Captured, the next block crossfades from the input to the resynthesis.
+    Captured,
+    /// Resynthesizing the captured spectrum.
+    Frozen,
+    /// Released, the next block crossfades from the resynthesis back to the input.
+    Released,
+}
+
+///Captures a magnitude spectrum and resynthesizes it for as long as the freeze is held
+/// Resynthesis uses Hann windowed frames with 50% overlap, two frames per block. Engaging and
+/// releasing the freeze crossfades over one hop.
+pub struct SpectralFreeze
+{
+    magnitudes: Vec<Vec<f32>>,
+    phases: Vec<Vec<f32>>,
+    overlap_add: OverlapAdd,
+    frame_buffer: Vec<f32>,
+    states: Vec<ChannelState>,
+    rng: XorShift32,
+}
+
+impl SpectralFreeze
+{
+    pub fn new(channels: usize, max_window_size: usize) -> Self
+    {
+        Self {
+            magnitudes: vec![vec![0.0; max_window_size / 2 + 1]; channels],
+            phases: vec![vec![0.0; max_window_size / 2 + 1]; channels],
+            overlap_add: OverlapAdd::new(channels, max_window_size),
+            frame_buffer: vec![0.0; max_window_size],
+            states: vec![ChannelState::Live; channels],
+            rng: XorShift32::new(0x5EED),
+        }
+    }
+
+    pub fn is_captured(&self, channel: usize) -> bool
+    {
+        matches!(self.states[channel], ChannelState::Captured | ChannelState::Frozen)
+    }
+
+    /// Whether a channel still needs to fade back to the input in [`SpectralFreeze::fade_out()`].
+    pub fn is_releasing(&self) -> bool
+    {
+        self.states.contains(&ChannelState::Released)
+    }
+
+    /// Let go of the captured spectra. Channels that were resynthesizing fade back to the input in
+    /// their next block, the block after that will be captured again.
+    pub fn release(&mut self)
+    {
+        for state in self.states.iter_mut()
+        {
+            *state = match *state
+            {
+                ChannelState::Frozen => ChannelState::Released,
+                ChannelState::Captured => ChannelState::Live,
+                state => state,
+            };
+        }
+    }
+
+    /// Drop the captured spectra and the resynthesis tails without fading out, for when the FFT
+    /// size changes or the plugin is reset.
+    pub fn reset(&mut self)
+    {
+        if self.states.iter().all(|state| *state == ChannelState::Live)
+        {
+            return;
+        }
+        self.states.fill(ChannelState::Live);
+        self.overlap_add.reset();
+    }
+
+    /// Store the magnitudes and phases of `spectrum`. The spectrum should already be gain
+    /// compensated.
+    pub fn capture(&mut self, channel: usize, spectrum: &[Complex32])
+    {
+        for ((mag, phase), c) in self.magnitudes[channel].iter_mut()
+            .zip(self.phases[channel].iter_mut())
+            .zip(spectrum)
+        {
+            *mag = c.norm();
+            *phase = c.arg();
+        }
+        self.states[channel] = ChannelState::Captured;
+    }
+
+    /// Resynthesize one block of the captured spectrum into `block`, which holds the input block.
+    /// The first block after the capture fades the input out while the resynthesis fades in.
+    /// `spectrum` is used as the IFFT input and must be `block.len() / 2 + 1` bins long, `window`
+    /// must be at least `block.len()` samples long.
+    pub fn resynthesize(
+        &mut self,
+        channel: usize,
+        mode: FreezePhaseMode,
+        window: &[f32],
+        spectrum: &mut [Complex32],
+        fft: &mut FftPlans,
+        block: &mut [f32],
+    )
+    {
+        let window_size = block.len();
+        let hop = window_size / 2;
+        let num_bins = spectrum.len();
+        let magnitudes = &self.magnitudes[channel][..num_bins];
+        let phases = &mut self.phases[channel][..num_bins];
+        let frame = &mut self.frame_buffer[..window_size];
+
+        self.overlap_add.start_block(channel, window_size);
+        for frame_idx in 0..2
+        {
+            for (bin, (phase, (mag, c))) in phases.iter_mut()
+                .zip(magnitudes.iter().zip(spectrum.iter_mut()))
+                .enumerate()
+            {
+                *phase = match mode
+                {
+                    FreezePhaseMode::Random => self.rng.next_f32() * TAU,
+                    //A hop of half a window advances bin k by k * pi
+                    FreezePhaseMode::Advancing => (*phase + bin as f32 * std::f32::consts::PI).rem_euclid(TAU),
+                };
+                *c = Complex32::from_polar(*mag, *phase);
+            }
+
+            fft.inverse(spectrum, frame);
+            frame.iter_mut().zip(window).for_each(|(sample, w)| *sample *= w);
+            self.overlap_add.add(channel, frame_idx * hop, frame);
+        }
+
+        // The first frame rises with the first half of the window, the input falls with its
+        // complement
+        if self.states[channel] == ChannelState::Captured
+        {
+            block[..hop].iter_mut().zip(window).for_each(|(input, w)| *input *= 1.0 - w);
+            self.overlap_add.add(channel, 0, &block[..hop]);
+            self.states[channel] = ChannelState::Frozen;
+        }
+
+        self.overlap_add.finish_block(channel, block);
+    }
+
+    /// Crossfade a released channel's input `block` with the tail of the resynthesis. The tail
+    /// falls with the second half of the window, the input rises with the first half. Blocks of
+    /// channels that aren't released are left alone.
+    pub fn fade_out(&mut self, channel: usize, window: &[f32], block: &mut [f32])
+    {
+        if self.states[channel] != ChannelState::Released
+        {
+            return;
+        }
+
+        let tail = self.overlap_add.tail(channel, block.len());
+        for ((sample, tail), w) in block.iter_mut().zip(tail).zip(window)
+        {
+            *sample = *sample * w + tail;
+        }
+        self.overlap_add.clear(channel);
+        self.states[channel] = ChannelState::Live;
+    }
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+    use realfft::RealFftPlanner;
+
+    #[test]
+    fn frozen_sine_keeps_its_level()
+    {
+        const WINDOW_SIZE: usize = 1024;
+        const BIN: usize = 32;
+        const AMPLITUDE: f32 = 0.5;
+
+        let mut planner = RealFftPlanner::new();
+        let r2c = planner.plan_fft_forward(WINDOW_SIZE);
+        let c2r = planner.plan_fft_inverse(WINDOW_SIZE);
+        let mut scratch = vec![Complex32::new(0.0, 0.0); r2c.get_scratch_len().max(c2r.get_scratch_len())];
+        let mut spectrum = vec![Complex32::new(0.0, 0.0); WINDOW_SIZE / 2 + 1];
+        // Zeroed like the buffer allocated in `initialize()`
+        let mut window = vec![0.0; WINDOW_SIZE];
+        resynthesis_window(&mut window);
+
+        // Captured the same way as in the FFT path, gain compensated
+        let mut block: Vec<f32> = (0..WINDOW_SIZE)
+            .map(|i| AMPLITUDE * (TAU * (BIN * i) as f32 / WINDOW_SIZE as f32).sin())
+            .collect();
+        r2c.process_with_scratch(&mut block, &mut spectrum, &mut scratch).unwrap();
+        spectrum.iter_mut().for_each(|c| *c /= WINDOW_SIZE as f32);
+        let mut freeze = SpectralFreeze::new(1, WINDOW_SIZE);
+        freeze.capture(0, &spectrum);
+
+        // The input is silent, so the first block only contains the fade in of the first frame
+        let mut output = vec![0.0; WINDOW_SIZE];
+        let mut fft = FftPlans { r2c: r2c.as_ref(), c2r: c2r.as_ref(), scratch: &mut scratch };
+        for _ in 0..3
+        {
+            output.fill(0.0);
+            freeze.resynthesize(0, FreezePhaseMode::Advancing, &window, &mut spectrum, &mut fft, &mut output);
+        }
+
+        let rms = (output.iter().map(|sample| sample * sample).sum::<f32>() / WINDOW_SIZE as f32).sqrt();
+        let expected = AMPLITUDE / 2.0f32.sqrt();
+        assert!((rms - expected).abs() < expected * 0.1, "RMS {rms}, expected {expected}");
+    }
+
+    #[test]
+    fn engaging_and_releasing_crossfade_with_the_input()
+    {
+        const WINDOW_SIZE: usize = 1024;
+        const BIN: usize = 32;
+
+        let mut planner = RealFftPlanner::new();
+        let r2c = planner.plan_fft_forward(WINDOW_SIZE);
+        let c2r = planner.plan_fft_inverse(WINDOW_SIZE);
+        let mut scratch = vec![Complex32::new(0.0, 0.0); r2c.get_scratch_len().max(c2r.get_scratch_len())];
+        let mut spectrum = vec![Complex32::new(0.0, 0.0); WINDOW_SIZE / 2 + 1];
+        let mut window = vec![0.0; WINDOW_SIZE];
+        resynthesis_window(&mut window);
+        let mut fft = FftPlans { r2c: r2c.as_ref(), c2r: c2r.as_ref(), scratch: &mut scratch };
+
+        // A sine that fits the block exactly, so every block and every frozen frame is the same
+        let input: Vec<f32> = (0..WINDOW_SIZE)
+            .map(|i| (TAU * (BIN * i) as f32 / WINDOW_SIZE as f32).sin())
+            .collect();
+        let mut block = input.clone();
+        fft.forward(&mut block, &mut spectrum);
+        spectrum.iter_mut().for_each(|c| *c /= WINDOW_SIZE as f32);
+        let mut freeze = SpectralFreeze::new(1, WINDOW_SIZE);
+        freeze.capture(0, &spectrum);
+
+        let max_error = |block: &[f32]| block.iter().zip(&input).map(|(a, b)| (a - b).abs()).fold(0.0f32, f32::max);
+        let mut block = input.clone();
+        freeze.resynthesize(0, FreezePhaseMode::Advancing, &window, &mut spectrum, &mut fft, &mut block);
+        assert!(max_error(&block) < 0.02, "engaging is off by {}", max_error(&block));
+
+        freeze.release();
+        assert!(freeze.is_releasing());
+        let mut block = input.clone();
+        freeze.fade_out(0, &window, &mut block);
+        assert!(max_error(&block) < 0.02, "releasing is off by {}", max_error(&block));
+        assert!(!freeze.is_releasing());
+    }
+}
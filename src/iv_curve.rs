@@ -0,0 +1,44 @@
+use crate::junction::{Junction, JunctionSettings};
+
+/// The number of bias currents the sweep evaluates.
+pub const IV_CURVE_POINTS: usize = 101;
+/// The bias current sweep covers this many times the critical current in both directions.
+pub const IV_CURVE_RANGE: f32 = 2.5;
+/// Samples run at every bias point before and while the voltage is averaged.
+const SETTLE_SAMPLES: usize = 256;
+const AVERAGE_SAMPLES: usize = 2048;
+
+/// Sweep a DC bias current through a resistively shunted junction and return the time averaged
+/// voltage for every bias, as `(voltage, current)` pairs.
+///
+/// The junction is the same one `process()` uses. The shunt's voltage drives the junction as the
+/// input's slope through [`Junction::process_diff`], so the phase gain acts as the integration
+/// step. Large phase gains make the simulation as unstable as the junction sounds, which shows up
+/// in the curve.
+pub fn iv_curve(settings: &JunctionSettings) -> Vec<(f32, f32)> {
+    let max_current = settings.critical_current.abs().max(f32::EPSILON) * IV_CURVE_RANGE;
+    // Keeps the unstable settings from running off to infinity
+    let max_voltage = max_current * 4.0;
+
+    (0..IV_CURVE_POINTS)
+        .map(|i| {
+            let bias = (i as f32 / (IV_CURVE_POINTS - 1) as f32 * 2.0 - 1.0) * max_current;
+            let mut junction = Junction::default();
+            let mut supercurrent = 0.0;
+            let mut voltage_sum = 0.0f64;
+            for n in 0..SETTLE_SAMPLES + AVERAGE_SAMPLES {
+                // Whatever bias current the junction doesn't carry flows through the shunt
+                let voltage = (bias - supercurrent).clamp(-max_voltage, max_voltage);
+                supercurrent = junction.process_diff(voltage, settings);
+                if !supercurrent.is_finite() {
+                    supercurrent = 0.0;
+                }
+                if n >= SETTLE_SAMPLES {
+                    voltage_sum += voltage as f64;
+                }
+            }
+
+            ((voltage_sum / AVERAGE_SAMPLES as f64) as f32, bias)
+        })
+        .collect()
+}
@@ -0,0 +1,219 @@
+use core::f32;
+
+use crate::cpr::CurrentPhaseRelation;
+use crate::util;
+
+/// The per-sample settings for a [`Junction`].
+#[derive(Clone, Copy)]
+pub struct JunctionSettings<'a> {
+    pub phase_gain: f32,
+    pub critical_current: f32,
+    pub invert_phase: bool,
+    /// Quantize the phase to this many steps per revolution before computing the output, or
+    /// leave it continuous when this is 0.
+    pub quantize_steps: u32,
+    /// How far past a step boundary the phase needs to move before the quantized phase follows,
+    /// as a fraction of a step.
+    pub quantize_hysteresis: f32,
+    /// Shifts the operating point of the sine, in radians.
+    pub phase_offset: f32,
+    /// Maps the phase to the supercurrent.
+    pub cpr: &'a CurrentPhaseRelation,
+}
+
+/// A single Josephson junction. The input drives the phase through its derivative, the output is
+/// the supercurrent `I_c * cpr(phase)`, where the current-phase relation is `sin(phase)` for an
+/// ideal junction.
+///
+/// The phase is kept wrapped to `[-pi, pi)` with the whole revolutions counted separately. A
+/// single accumulated `f32` would lose precision the longer the junction runs.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Junction {
+    prev: f32,
+    /// The phase within the current revolution, in `[-pi, pi)`.
+    phase: f32,
+    /// The number of whole revolutions (flux quanta) the phase has slipped.
+    revolutions: i64,
+    /// The phase change of the last sample, proportional to the junction voltage.
+    dphi: f32,
+    /// The current step when the phase is quantized, counted from zero phase and zero
+    /// revolutions.
+    step: i64,
+}
+
+impl Junction {
+    pub fn process(&mut self, input: f32, settings: &JunctionSettings) -> f32 {
+        let diff = self.prev - input;
+        self.prev = input;
+        //prevent NaN poisoning
+        if self.prev.is_nan() {
+            self.prev = 0.0;
+        }
+
+        self.process_diff(diff, settings)
+    }
+
+    /// Drive the junction with the input's slope directly, `prev - input` in terms of
+    /// [`Junction::process`]. This is the voltage across the junction.
+    pub fn process_diff(&mut self, diff: f32, settings: &JunctionSettings) -> f32 {
+        let dphi = if settings.invert_phase {
+            util::map_range_linear(1.0 / diff, 0.0, 1.0 / f32::EPSILON, 0.0, 1.0) * settings.phase_gain
+        } else {
+            diff * settings.phase_gain
+        };
+
+        self.advance_phase(dphi);
+
+        let phase = self.quantized_phase(settings) + settings.phase_offset;
+        if settings.invert_phase {
+            diff * settings.critical_current * settings.cpr.current(phase)
+        } else {
+            settings.critical_current * settings.cpr.current(phase)
+        }
+    }
+
+    /// Add `dphi` to the phase, moving whole revolutions to the revolution counter.
+    fn advance_phase(&mut self, dphi: f32) {
+        if !dphi.is_finite() {
+            return;
+        }
+
+        self.dphi = dphi;
+        // Move the whole turns to the counter first. Adding a step of millions of radians to the
+        // phase directly would round away the position within the revolution
+        let remainder = dphi % f32::consts::TAU;
+        self.revolutions += ((dphi as f64 - remainder as f64) / f32::consts::TAU as f64).round() as i64;
+        self.phase += remainder;
+        if !(-f32::consts::PI..f32::consts::PI).contains(&self.phase) {
+            let turns = ((self.phase + f32::consts::PI) / f32::consts::TAU).floor();
+            self.phase -= turns * f32::consts::TAU;
+            self.revolutions += turns as i64;
+            // Rounding can leave the phase exactly on pi
+            if self.phase >= f32::consts::PI {
+                self.phase -= f32::consts::TAU;
+                self.revolutions += 1;
+            }
+        }
+    }
+
+    /// The phase snapped to one of `quantize_steps` flux quanta per revolution, relative to the
+    /// current revolution.
+    fn quantized_phase(&mut self, settings: &JunctionSettings) -> f32 {
+        if settings.quantize_steps == 0 {
+            return self.phase;
+        }
+
+        let steps = settings.quantize_steps as i64;
+        let step_size = f32::consts::TAU / settings.quantize_steps as f32;
+        let position = (self.revolutions * steps) as f64 + (self.phase / step_size) as f64;
+        if (position - self.step as f64).abs() > 0.5 + settings.quantize_hysteresis as f64 * 0.5 {
+            self.step = position.round() as i64;
+        }
+        (self.step - self.revolutions * steps) as f32 * step_size
+    }
+
+    /// The phase within the current revolution, in `[-pi, pi)`.
+    pub fn phase(&self) -> f32 {
+        self.phase
+    }
+
+    /// The phase change over the last sample. By the AC Josephson relation this is proportional
+    /// to the voltage across the junction.
+    pub fn dphi(&self) -> f32 {
+        self.dphi
+    }
+
+    /// The number of whole revolutions the phase has slipped, negative for backwards rotation.
+    pub fn revolutions(&self) -> i64 {
+        self.revolutions
+    }
+
+    /// Set the phase back to zero without touching the differentiator.
+    pub fn reset_phase(&mut self) {
+        self.phase = 0.0;
+        self.revolutions = 0;
+        self.step = 0;
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Low enough to get through hours of input quickly.
+    const SAMPLE_RATE: f64 = 1000.0;
+
+    #[test]
+    fn huge_phase_steps_keep_the_phase_in_range() {
+        let mut junction = Junction::default();
+        let mut total = 0.0f64;
+        // Steps this large come from the inverted phase mode on the tiny slopes of a fade out
+        for i in 0..2000 {
+            let sign = if i % 2 == 0 { 1.0 } else { -1.0 };
+            let dphi = sign * (1e7 + i as f32 * 3e4);
+            junction.advance_phase(dphi);
+            total += dphi as f64;
+
+            assert!(
+                (-f32::consts::PI..f32::consts::PI).contains(&junction.phase()),
+                "phase {} is out of range after a step of {dphi}",
+                junction.phase()
+            );
+            let unwrapped = junction.revolutions() as f64 * f32::consts::TAU as f64 + junction.phase() as f64;
+            assert!((unwrapped - total).abs() < 1e-2, "phase is at {unwrapped} instead of {total}");
+        }
+    }
+
+    #[test]
+    fn hours_of_input_stay_continuous() {
+        const HOURS: f64 = 2.0;
+        const PHASE_GAIN: f32 = 1e5;
+
+        let cpr = CurrentPhaseRelation::default();
+        let settings = JunctionSettings {
+            phase_gain: PHASE_GAIN,
+            critical_current: 1.0,
+            invert_phase: false,
+            quantize_steps: 0,
+            quantize_hysteresis: 0.0,
+            phase_offset: 0.0,
+            cpr: &cpr,
+        };
+        let mut junction = Junction::default();
+
+        // A slow sweep turns the phase through tens of thousands of revolutions and back, well
+        // past where an accumulated f32 phase would have lost its precision
+        let num_samples = (HOURS * 3600.0 * SAMPLE_RATE) as u64;
+        let mut prev_input = 0.0f32;
+        let mut prev_output = junction.process(prev_input, &settings);
+        let mut max_revolutions = 0;
+        for t in 1..num_samples {
+            let time = t as f64 / SAMPLE_RATE;
+            let input = (time * 0.01 * std::f64::consts::TAU).sin() as f32;
+            let output = junction.process(input, &settings);
+
+            assert!(output.is_finite(), "output is not finite after {time} s");
+            assert!(
+                (-f32::consts::PI..f32::consts::PI).contains(&junction.phase()),
+                "phase {} is out of range after {time} s",
+                junction.phase()
+            );
+            // The sine can't change faster than the phase
+            let bound = PHASE_GAIN * (input - prev_input).abs() + 1e-4;
+            assert!(
+                (output - prev_output).abs() <= bound,
+                "output jumped from {prev_output} to {output} after {time} s"
+            );
+
+            max_revolutions = max_revolutions.max(junction.revolutions().abs());
+            prev_input = input;
+            prev_output = output;
+        }
+
+        assert!(max_revolutions > 10_000, "only reached {max_revolutions} revolutions");
+    }
+}
@@ -1,10 +1,9 @@
 use core::f32;
-use fft_filter::FFTHelper;
+use fft_filter::{FFTHelper, FftPlans};
 
 
 use nih_plug::prelude::*;
 use nih_plug::prelude::util::db_to_gain;
-use nih_plug::prelude::util as nih_util;
 
 use nih_plug_iced::IcedState;
 use realfft::{
@@ -12,31 +11,73 @@ use realfft::{
     num_traits::{Inv},
     ComplexToReal, RealFftPlanner, RealToComplex,
 };
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::sync::{Arc, RwLock};
 
-use crate::editor::PrismatineEditorParams;
+use crate::cpr::{CprShape, CurrentPhaseRelation, USER_WAVETABLE_SIZE};
+use crate::editor::{ControlLayout, PrismatineEditorParams};
+use crate::emphasis::Emphasis;
+use crate::feedback::{Feedback, MAX_FEEDBACK_DELAY_MS};
+use crate::freeze::{FreezePhaseMode, SpectralFreeze};
+use crate::junction::{Junction, JunctionSettings};
+use crate::metering::{LevelFollower, Metering};
+use crate::morph::{Morph, MorphTargets};
+use crate::multiband::{BandParams, BandSwitchParams, Crossover, MAX_BANDS};
+use crate::ring_buffer::AtomicRingBuffer;
+use crate::snapshots::Snapshots;
+use crate::post_filter::{LinearPhaseFilter, PostFilter, PostFilterMode, PostFilterSettings};
+use crate::tempo_sync::{SyncDivision, TransportClock};
+use crate::theme::EditorTheme;
 
+mod cpr;
 mod editor;
+mod emphasis;
+mod feedback;
 mod fft_filter;
+mod freeze;
+mod iv_curve;
+mod junction;
+mod metering;
+mod morph;
+mod multiband;
+mod post_filter;
+mod presets;
+mod ring_buffer;
+mod snapshots;
+mod spectrum;
+mod tempo_sync;
+mod theme;
+mod undo;
 mod util;
+mod widgets;
 
 // FT stuff:
 // Sample rate ~ maximum frequency
 // Window size ~ minimum frequency
 // Number of samples ~ frequency resolution
 
-// The size of the windows we'll process at a time.
-const WINDOW_SIZE: usize = 1024;
+/// The smallest and largest FFT sizes selectable through the `fft_order` parameter, as powers of
+/// two. This gives a range of 256 to 8192 samples.
+const MIN_FFT_ORDER: i32 = 8;
+const MAX_FFT_ORDER: i32 = 13;
+/// The default FFT size, also as a power of two.
+const DEFAULT_FFT_ORDER: i32 = 10;
+/// The largest FFT window we'll ever need. Buffers are allocated for this size in `initialize()`
+/// so switching the FFT size on the audio thread never allocates.
+const MAX_FFT_WINDOW_SIZE: usize = 1 << MAX_FFT_ORDER;
+/// The longest latency the plugin can have, the largest FFT block plus the linear phase post
+/// filter.
+const MAX_LATENCY: usize = MAX_FFT_WINDOW_SIZE + LinearPhaseFilter::latency(MAX_FFT_WINDOW_SIZE);
 /// The length of the filter's impulse response.
 const FILTER_WINDOW_SIZE: usize = 0;
-/// The length of the FFT window we will use to perform FFT convolution. This includes padding to
-/// prevent time domain aliasing as a result of cyclic convolution.
-const FFT_WINDOW_SIZE: usize = WINDOW_SIZE; //+ FILTER_WINDOW_SIZE - 1;
 
-/// The gain compensation we need to apply for the STFT process.
-const GAIN_COMPENSATION: f32 = 1.0 / FFT_WINDOW_SIZE as f32;
+/// The number of phase values kept for the editor's phase circle trail.
+const PHASE_HISTORY_SIZE: usize = 256;
+/// Only every n-th sample's phase is sent to the phase circle.
+const PHASE_HISTORY_DECIMATION: usize = 32;
 
-const MAX_PHASE: f32 = 1.0e4 * f32::consts::PI;
+/// The time it takes for the band meters to decay by 12 dB.
+const PEAK_METER_DECAY_MS: f64 = 150.0;
 fn kinetic_spectrum_from_window_size(window_size: usize, sample_rate: f32) -> Vec<Complex<f32>> {
     let filter_spectrum: Vec<Complex32> = (0..window_size / 2)
         .map(|i| (i as f32) * sample_rate / (2.0 * window_size as f32)) //construced frequency values
@@ -66,18 +107,73 @@ pub struct Prismatine {
     /// The FFT of a simple low-pass FIR filter.
     filter_spectrum: Vec<Complex32>,
 
-    /// The algorithm for the FFT operation.
-    r2c_plan: Arc<dyn RealToComplex<f32>>,
-    /// The algorithm for the IFFT operation.
-    c2r_plan: Arc<dyn ComplexToReal<f32>>,
-    /// The output of our real->complex FFT.
+    /// The algorithms for the FFT operation, one for every selectable FFT size. Indexed by
+    /// `fft_order - MIN_FFT_ORDER`.
+    r2c_plans: Vec<Arc<dyn RealToComplex<f32>>>,
+    /// The algorithms for the IFFT operation, indexed the same way as `r2c_plans`.
+    c2r_plans: Vec<Arc<dyn ComplexToReal<f32>>>,
+    /// The FFT size the buffers and the STFT helper are currently set up for.
+    fft_window_size: usize,
+    /// The output of our real->complex FFT. Sized for `MAX_FFT_WINDOW_SIZE`, only the first
+    /// `fft_window_size / 2 + 1` bins are used.
     complex_fft_buffer: Vec<Complex32>,
 
-    scratch_buffer: [Complex32; 2048],
-    window_buff: [f32; FFT_WINDOW_SIZE],
+    /// Scratch space for the FFT algorithms, large enough for every plan in `r2c_plans` and
+    /// `c2r_plans`.
+    scratch_buffer: Vec<Complex32>,
+    window_buff: Vec<f32>,
 
-    prev: [f32; 2],
+    /// Holds the captured spectrum while `freeze` is enabled.
+    freeze: SpectralFreeze,
+
+    /// Low cut, high cut and tilt on the wet signal.
+    post_filter: PostFilter,
+    /// Applies `post_filter`'s response in the FFT path for the linear phase mode.
+    linear_phase: LinearPhaseFilter,
+    /// The latency last reported to the host.
+    latency_samples: u32,
+    /// Shelves before the differentiator and after the junction.
+    emphasis: Emphasis,
+    sample_rate: f32,
+
+    /// Splits the input into bands and runs one junction per channel and band.
+    bands: Bands,
+    /// The current-phase relation shared by all junctions.
+    cpr: CurrentPhaseRelation,
+    /// The morph targets and the current morph position.
+    morph: Morph,
+    /// When the morph targets differ in the band layout or the junction's discrete settings, these
+    /// bands run with the B settings and their output is crossfaded with `bands`.
+    morph_bands: Bands,
+    /// The current-phase relation for `morph_bands`.
+    morph_cpr: CurrentPhaseRelation,
+    /// Whether `morph_bands` ran during the last buffer.
+    morph_crossfading: bool,
+    /// The delayed output that's mixed back into the junction drive.
+    feedback: Feedback,
+    /// The index of the synced division the last sample fell in, `None` while the transport
+    /// isn't running.
+    sync_block: Option<i64>,
+
+    /// The phase of each channel's first band, shown in the editor.
     phase: Arc<[AtomicF32; 2]>,
+    /// Recent phase values of each channel's first band, for the editor's phase circle.
+    phase_history: Arc<[AtomicRingBuffer<PHASE_HISTORY_SIZE>; 2]>,
+    /// Counts samples for the phase history's decimation.
+    phase_history_counter: usize,
+    /// The revolution count of each channel's first band.
+    revolutions: Arc<[AtomicI64; 2]>,
+    /// The junction voltage of each channel's first band, as the phase change in radians per
+    /// second.
+    voltage: Arc<[AtomicF32; 2]>,
+    /// Input and output levels and the oscilloscope, for the editor.
+    metering: Arc<Metering>,
+    input_level: LevelFollower,
+    output_level: LevelFollower,
+    /// The peak output level of every band, for the editor's meters.
+    band_meters: Arc<[AtomicF32; MAX_BANDS]>,
+    /// How much the band meters decay per sample.
+    peak_meter_decay_weight: f32,
 }
 
 #[derive(Params)]
@@ -85,6 +181,19 @@ struct PrismatineParams {
 
      #[persist = "editor-state"]
     editor_state: Arc<IcedState>,
+    /// The user's UI scale in the editor, on top of the host's HiDPI scaling.
+    #[persist = "editor-scale"]
+    editor_scale: RwLock<f32>,
+    #[persist = "editor-theme"]
+    editor_theme: RwLock<EditorTheme>,
+    #[persist = "editor-layout"]
+    editor_layout: RwLock<ControlLayout>,
+    /// The name of the last loaded or saved preset, shown in the preset browser.
+    #[persist = "editor-preset"]
+    editor_preset: RwLock<String>,
+    /// The A/B comparison slots.
+    #[persist = "snapshots"]
+    snapshots: RwLock<Snapshots>,
     //TODO: Dry/Wet
     #[id = "phase_gain"]
     phase_gain: FloatParam,
@@ -92,75 +201,522 @@ struct PrismatineParams {
     #[id = "I_c"]
     I_c: FloatParam,
 
+    /// How many bands the input is split into, each with its own junction.
+    #[id = "num_bands"]
+    num_bands: IntParam,
+
+    #[id = "crossover_1"]
+    crossover_1: FloatParam,
+
+    #[id = "crossover_2"]
+    crossover_2: FloatParam,
+
+    #[id = "crossover_3"]
+    crossover_3: FloatParam,
+
+    #[nested(id_prefix = "band_2", group = "Band 2")]
+    band_2: BandParams,
+
+    #[nested(id_prefix = "band_3", group = "Band 3")]
+    band_3: BandParams,
+
+    #[nested(id_prefix = "band_4", group = "Band 4")]
+    band_4: BandParams,
+
+    #[nested(array, group = "Band")]
+    band_switches: [BandSwitchParams; MAX_BANDS],
+
     #[id = "invert_phase"]
     invert_phase: BoolParam,
 
+    #[id = "cpr_shape"]
+    cpr_shape: EnumParam<CprShape>,
+
+    /// The transparency for the skewed sine, or the second harmonic's level for the harmonic mix.
+    #[id = "cpr_amount"]
+    cpr_amount: FloatParam,
+
+    /// The points of the user drawn current-phase relation, one revolution.
+    #[persist = "cpr-wavetable"]
+    cpr_wavetable: RwLock<Vec<f32>>,
+    /// Set by the editor when `cpr_wavetable` was changed so the audio thread can pick it up.
+    cpr_wavetable_changed: AtomicBool,
+
+    /// Quantizes the phase to this many steps per revolution, 0 disables the quantization.
+    #[id = "phase_steps"]
+    phase_steps: IntParam,
+
+    #[id = "phase_hysteresis"]
+    phase_hysteresis: FloatParam,
+
+    /// Shifts the operating point of the sine.
+    #[id = "phase_offset"]
+    phase_offset: FloatParam,
+
+    /// Resets the phase and the revolution counter every synced division. The phase always stays
+    /// within one revolution, so there's nothing to wrap, wrapping would only clear the counter.
+    #[id = "phase_sync"]
+    phase_sync: EnumParam<SyncDivision>,
+
+    /// The period of the transport locked phase offset LFO.
+    #[id = "lfo_rate"]
+    lfo_rate: EnumParam<SyncDivision>,
+
+    #[id = "lfo_depth"]
+    lfo_depth: FloatParam,
+
+    /// How much of the junction's output is mixed back into its input. Negative values invert
+    /// the feedback.
+    #[id = "feedback"]
+    feedback: FloatParam,
+
+    /// The delay of the feedback path. The minimum feeds back the previous output sample.
+    #[id = "feedback_delay"]
+    feedback_delay: FloatParam,
+
+    /// The shelf frequency for the pre- and de-emphasis around the junction.
+    #[id = "emphasis_freq"]
+    emphasis_freq: FloatParam,
+
+    /// How much the highs are boosted before the junction and cut after it, in dB. Negative
+    /// values emphasize the lows instead.
+    #[id = "emphasis_amount"]
+    emphasis_amount: FloatParam,
+
     #[id = "remove_dc"]
     remove_dc: BoolParam,
+
+    /// The FFT size used for DC removal, as a power of two. Larger sizes give a finer frequency
+    /// resolution at the cost of more latency.
+    #[id = "fft_order"]
+    fft_order: IntParam,
+
+    /// Captures the wet signal's magnitude spectrum and keeps resynthesizing it while enabled.
+    #[id = "freeze"]
+    freeze: BoolParam,
+
+    #[id = "freeze_phase"]
+    freeze_phase: EnumParam<FreezePhaseMode>,
+
+    #[id = "post_eq"]
+    post_eq: BoolParam,
+
+    #[id = "post_eq_mode"]
+    post_eq_mode: EnumParam<PostFilterMode>,
+
+    #[id = "low_cut"]
+    low_cut: FloatParam,
+
+    #[id = "high_cut"]
+    high_cut: FloatParam,
+
+    /// Tilt around 1 kHz, positive values brighten the wet signal.
+    #[id = "tilt"]
+    tilt: FloatParam,
+
+    /// Sweeps between the two stored morph targets. Only the parameters that differ between them are
+    /// morphed, see [`Morph`] for which of those switch over halfway through.
+    #[id = "morph"]
+    morph: FloatParam,
+    #[persist = "morph-targets"]
+    morph_targets: RwLock<MorphTargets>,
+    /// Set when the morph targets change, so the audio thread picks up the new table.
+    morph_targets_changed: AtomicBool,
 }
 
 impl Default for Prismatine {
     fn default() -> Self {
-        let mut planner = RealFftPlanner::new();
-        let r2c_plan = planner.plan_fft_forward(FFT_WINDOW_SIZE);
-        let c2r_plan = planner.plan_fft_inverse(FFT_WINDOW_SIZE);
-        let complex_fft_buffer = r2c_plan.make_output_vec();
-        nih_dbg!(complex_fft_buffer.len());
-        
+        let fft_window_size = 1 << DEFAULT_FFT_ORDER;
+
+        // The FFT plans and buffers are (re)allocated in `initialize()`
         Self {
             params: Arc::new(PrismatineParams::default()),
-            stft: FFTHelper::new(2, WINDOW_SIZE),
+            stft: FFTHelper::new(2, fft_window_size),
 
-            filter_spectrum: vec![Complex32 { re: 0.0, im: 0.0 }; complex_fft_buffer.len()],
+            filter_spectrum: Vec::new(),
 
-            r2c_plan,
-            c2r_plan,
-            complex_fft_buffer,
-            scratch_buffer: [Complex32::new(0.0, 0.0); 2048],
-            window_buff: [0.0; FFT_WINDOW_SIZE],
-            prev: [0.0; 2],
+            r2c_plans: Vec::new(),
+            c2r_plans: Vec::new(),
+            fft_window_size,
+            complex_fft_buffer: Vec::new(),
+            scratch_buffer: Vec::new(),
+            window_buff: Vec::new(),
+            freeze: SpectralFreeze::new(2, 0),
+            post_filter: PostFilter::default(),
+            linear_phase: LinearPhaseFilter::new(2, 0),
+            latency_samples: 0,
+            emphasis: Emphasis::default(),
+            sample_rate: 44100.0,
+            bands: Bands::default(),
+            cpr: CurrentPhaseRelation::default(),
+            morph: Morph::default(),
+            morph_bands: Bands::default(),
+            morph_cpr: CurrentPhaseRelation::default(),
+            morph_crossfading: false,
+            feedback: Feedback::new(2, 0.0),
+            sync_block: None,
             phase: Arc::new([AtomicF32::new(0.0), AtomicF32::new(0.0)]),
+            phase_history: Arc::new(Default::default()),
+            phase_history_counter: 0,
+            revolutions: Arc::new([AtomicI64::new(0), AtomicI64::new(0)]),
+            voltage: Arc::new([AtomicF32::new(0.0), AtomicF32::new(0.0)]),
+            metering: Arc::new(Metering::default()),
+            input_level: LevelFollower::default(),
+            output_level: LevelFollower::default(),
+            band_meters: Arc::new(std::array::from_fn(|_| AtomicF32::new(0.0))),
+            peak_meter_decay_weight: 1.0,
         }
     }
 }
 
+fn phase_gain_param() -> FloatParam {
+    FloatParam::new(
+        "Phase Gain",
+        db_to_gain(0.0),
+        FloatRange::Skewed {
+            min: db_to_gain(0.0),
+            max: db_to_gain(60.0),
+            factor: FloatRange::gain_skew_factor(0.0, 60.0),
+        },
+    )
+    .with_smoother(SmoothingStyle::Logarithmic(50.0))
+    .with_unit(" dB")
+    .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+    .with_string_to_value(formatters::s2v_f32_gain_to_db())
+}
+
+fn critical_current_param() -> FloatParam {
+    FloatParam::new(
+        "Critical Current",
+        db_to_gain(0.0),
+        FloatRange::Skewed {
+            min: db_to_gain(-30.0),
+            max: db_to_gain(30.0),
+            factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+        },
+    )
+    .with_smoother(SmoothingStyle::Logarithmic(50.0))
+    .with_unit(" dB")
+    .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
+    .with_string_to_value(formatters::s2v_f32_gain_to_db())
+}
+
+fn crossover_param(name: &str, default: f32) -> FloatParam {
+    FloatParam::new(
+        name,
+        default,
+        FloatRange::Skewed {
+            min: 40.0,
+            max: 16000.0,
+            factor: FloatRange::skew_factor(-2.0),
+        },
+    )
+    .with_smoother(SmoothingStyle::Logarithmic(50.0))
+    .with_unit(" Hz")
+    .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+    .with_string_to_value(formatters::s2v_f32_hz_then_khz())
+}
+
 impl Default for PrismatineParams {
     fn default() -> Self {
         Self {
             editor_state: editor::default_state(),
+            editor_scale: RwLock::new(1.0),
+            editor_theme: RwLock::new(EditorTheme::default()),
+            editor_layout: RwLock::new(ControlLayout::default()),
+            editor_preset: RwLock::new(String::new()),
+            snapshots: RwLock::new(Snapshots::default()),
 
-            phase_gain: FloatParam::new(
-                "Phase Gain",
-                db_to_gain(0.0),
+            phase_gain: phase_gain_param(),
+            I_c: critical_current_param(),
+            num_bands: IntParam::new("Bands", 1, IntRange::Linear { min: 1, max: MAX_BANDS as i32 }),
+            crossover_1: crossover_param("Crossover 1", 200.0),
+            crossover_2: crossover_param("Crossover 2", 1000.0),
+            crossover_3: crossover_param("Crossover 3", 5000.0),
+            band_2: BandParams::default(),
+            band_3: BandParams::default(),
+            band_4: BandParams::default(),
+            band_switches: Default::default(),
+            invert_phase: BoolParam::new("Invert Phase", false),
+            cpr_shape: EnumParam::new("Current-Phase Relation", CprShape::Sine),
+            cpr_amount: FloatParam::new("Shape Amount", 0.5, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(50.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            cpr_wavetable: RwLock::new(
+                (0..USER_WAVETABLE_SIZE)
+                    .map(|i| (i as f32 / USER_WAVETABLE_SIZE as f32 * f32::consts::TAU).sin())
+                    .collect(),
+            ),
+            cpr_wavetable_changed: AtomicBool::new(true),
+            phase_steps: IntParam::new("Phase Steps", 0, IntRange::Linear { min: 0, max: 64 })
+                .with_value_to_string(Arc::new(|steps| match steps {
+                    0 => String::from("Off"),
+                    steps => steps.to_string(),
+                }))
+                .with_string_to_value(Arc::new(|string| match string.trim() {
+                    s if s.eq_ignore_ascii_case("off") => Some(0),
+                    s => s.parse().ok(),
+                })),
+            phase_hysteresis: FloatParam::new(
+                "Phase Hysteresis",
+                0.0,
+                FloatRange::Linear { min: 0.0, max: 1.0 },
+            )
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            phase_offset: FloatParam::new(
+                "Phase Offset",
+                0.0,
+                FloatRange::Linear {
+                    min: -180.0,
+                    max: 180.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("°")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            phase_sync: EnumParam::new("Phase Sync", SyncDivision::Off),
+            lfo_rate: EnumParam::new("LFO Rate", SyncDivision::Off),
+            lfo_depth: FloatParam::new(
+                "LFO Depth",
+                0.0,
+                FloatRange::Linear {
+                    min: 0.0,
+                    max: 180.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("°")
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            feedback: FloatParam::new(
+                "Feedback",
+                0.0,
+                FloatRange::Linear {
+                    min: -1.0,
+                    max: 1.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(20.0))
+            .with_unit("%")
+            .with_value_to_string(formatters::v2s_f32_percentage(0))
+            .with_string_to_value(formatters::s2v_f32_percentage()),
+            feedback_delay: FloatParam::new(
+                "Feedback Delay",
+                0.0,
+                FloatRange::Skewed {
+                    min: 0.0,
+                    max: MAX_FEEDBACK_DELAY_MS,
+                    factor: FloatRange::skew_factor(-1.5),
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
+            .with_unit(" ms")
+            .with_value_to_string(formatters::v2s_f32_rounded(2)),
+            emphasis_freq: FloatParam::new(
+                "Emphasis Frequency",
+                1000.0,
                 FloatRange::Skewed {
-                    min: db_to_gain(0.0),
-                    max: db_to_gain(60.0),
-                    factor: FloatRange::gain_skew_factor(0.0, 60.0),
+                    min: 100.0,
+                    max: 10000.0,
+                    factor: FloatRange::skew_factor(-1.0),
                 },
             )
             .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            emphasis_amount: FloatParam::new(
+                "Emphasis Amount",
+                0.0,
+                FloatRange::Linear {
+                    min: -24.0,
+                    max: 24.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
             .with_unit(" dB")
-            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
-            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
-            I_c: FloatParam::new(
-                "Critical Current",
-                db_to_gain(0.0),
+            .with_value_to_string(formatters::v2s_f32_rounded(1)),
+            remove_dc: BoolParam::new("Remove DC", false),
+            fft_order: IntParam::new(
+                "FFT Size",
+                DEFAULT_FFT_ORDER,
+                IntRange::Linear {
+                    min: MIN_FFT_ORDER,
+                    max: MAX_FFT_ORDER,
+                },
+            )
+            .with_value_to_string(Arc::new(|order| (1usize << order).to_string()))
+            .with_string_to_value(Arc::new(|string| {
+                let size = string.trim().parse::<usize>().ok()?;
+                size.is_power_of_two()
+                    .then(|| size.trailing_zeros() as i32)
+                    .filter(|order| (MIN_FFT_ORDER..=MAX_FFT_ORDER).contains(order))
+            })),
+            freeze: BoolParam::new("Freeze", false),
+            freeze_phase: EnumParam::new("Freeze Phase", FreezePhaseMode::Advancing),
+            post_eq: BoolParam::new("Post EQ", false),
+            post_eq_mode: EnumParam::new("Post EQ Mode", PostFilterMode::MinimumPhase),
+            low_cut: FloatParam::new(
+                "Low Cut",
+                20.0,
+                FloatRange::Skewed {
+                    min: 20.0,
+                    max: 2000.0,
+                    factor: FloatRange::skew_factor(-1.0),
+                },
+            )
+            .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            high_cut: FloatParam::new(
+                "High Cut",
+                20000.0,
                 FloatRange::Skewed {
-                    min: db_to_gain(-30.0),
-                    max: db_to_gain(30.0),
-                    factor: FloatRange::gain_skew_factor(-30.0, 30.0),
+                    min: 1000.0,
+                    max: 20000.0,
+                    factor: FloatRange::skew_factor(-1.0),
                 },
             )
             .with_smoother(SmoothingStyle::Logarithmic(50.0))
+            .with_unit(" Hz")
+            .with_value_to_string(formatters::v2s_f32_hz_then_khz(0))
+            .with_string_to_value(formatters::s2v_f32_hz_then_khz()),
+            tilt: FloatParam::new(
+                "Tilt",
+                0.0,
+                FloatRange::Linear {
+                    min: -12.0,
+                    max: 12.0,
+                },
+            )
+            .with_smoother(SmoothingStyle::Linear(50.0))
             .with_unit(" dB")
-            .with_value_to_string(formatters::v2s_f32_gain_to_db(2))
-            .with_string_to_value(formatters::s2v_f32_gain_to_db()),
-            invert_phase: BoolParam::new("Invert Phase", false),
-            remove_dc: BoolParam::new("Remove DC", false)
+            .with_value_to_string(formatters::v2s_f32_rounded(1))
+            .with_string_to_value(Arc::new(|string| {
+                string.trim().trim_end_matches("dB").trim_end().parse().ok()
+            })),
+            morph: FloatParam::new("Morph", 0.0, FloatRange::Linear { min: 0.0, max: 1.0 })
+                .with_smoother(SmoothingStyle::Linear(20.0))
+                .with_unit("%")
+                .with_value_to_string(formatters::v2s_f32_percentage(0))
+                .with_string_to_value(formatters::s2v_f32_percentage()),
+            morph_targets: RwLock::new(MorphTargets::default()),
+            morph_targets_changed: AtomicBool::new(true),
+        }
+    }
+}
+
+impl Prismatine {
+    /// The latency of the FFT path, or 0 while it's idle and the output isn't delayed. The linear
+    /// phase post filter adds its own delay to the block.
+    fn latency_samples(fft_window_size: usize, fft_path: bool, linear_phase_eq: bool) -> u32 {
+        if !fft_path
+        {
+            return 0;
+        }
+        let filter_latency = if linear_phase_eq { LinearPhaseFilter::latency(fft_window_size) } else { 0 };
+        (fft_window_size + filter_latency) as u32
+    }
+
+    /// Switch the STFT helper and the window over to a new FFT size. The buffers must already be
+    /// large enough for `fft_window_size`, which is the case after `initialize()`.
+    fn set_fft_window_size(&mut self, fft_window_size: usize) {
+        self.fft_window_size = fft_window_size;
+        self.stft.set_block_size(fft_window_size);
+        self.freeze.reset();
+        self.linear_phase.reset();
+        freeze::resynthesis_window(&mut self.window_buff[..fft_window_size]);
+    }
+
+    /// The number of bands and the band switches at the A or B end of the morph.
+    fn band_layout(&self, b: bool) -> BandLayout {
+        let switches = &self.params.band_switches;
+        BandLayout {
+            num_bands: self.morph.discrete_at(&self.params.num_bands, b) as usize,
+            solo: std::array::from_fn(|band| self.morph.discrete_at(&switches[band].solo, b)),
+            bypass: std::array::from_fn(|band| self.morph.discrete_at(&switches[band].bypass, b)),
         }
     }
 }
 
+/// How many bands are used, and which are soloed or bypassed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BandLayout {
+    num_bands: usize,
+    solo: [bool; MAX_BANDS],
+    bypass: [bool; MAX_BANDS],
+}
+
+/// The crossover and the junctions for every channel and band.
+#[derive(Clone, Default)]
+struct Bands {
+    crossover: Crossover,
+    junctions: [[Junction; MAX_BANDS]; 2],
+}
+
+impl Bands {
+    /// Split one channel's `input` into bands, run them through their junctions and sum them back
+    /// up. Bypassed bands skip their junction, and none of the junctions run while `silent`. Each
+    /// band's peak level is kept in `peaks`.
+    fn process(
+        &mut self,
+        channel: usize,
+        input: f32,
+        silent: bool,
+        layout: &BandLayout,
+        settings: &[JunctionSettings; MAX_BANDS],
+        peaks: &mut [f32; MAX_BANDS],
+    ) -> f32 {
+        let num_bands = layout.num_bands;
+        let mut bands = [0.0; MAX_BANDS];
+        self.crossover.split(channel, input, &mut bands[..num_bands]);
+
+        let any_solo = layout.solo[..num_bands].iter().any(|solo| *solo);
+        let mut output = 0.0;
+        for (band, band_sample) in bands[..num_bands].iter().enumerate() {
+            let band_out = if layout.bypass[band] {
+                *band_sample
+            } else if silent {
+                0.0
+            } else {
+                self.junctions[channel][band].process(*band_sample, &settings[band])
+            };
+            peaks[band] = peaks[band].max(band_out.abs());
+            if !any_solo || layout.solo[band] {
+                output += band_out;
+            }
+        }
+
+        output
+    }
+
+    fn reset(&mut self) {
+        self.crossover.reset();
+        self.junctions.iter_mut().flatten().for_each(Junction::reset);
+    }
+}
+
+impl PrismatineParams {
+    /// The phase gain and critical current parameters for a band, starting at 0.
+    fn band_junction_params(&self, band: usize) -> (&FloatParam, &FloatParam) {
+        match band {
+            0 => (&self.phase_gain, &self.I_c),
+            1 => (&self.band_2.phase_gain, &self.band_2.I_c),
+            2 => (&self.band_3.phase_gain, &self.band_3.I_c),
+            _ => (&self.band_4.phase_gain, &self.band_4.I_c),
+        }
+    }
+
+    /// The FFT size selected through the `fft_order` parameter, in samples.
+    fn fft_window_size(&self) -> usize {
+        1 << self.fft_order.value()
+    }
+}
+
 impl Plugin for Prismatine {
     const NAME: &'static str = "Prismatine";
     const VENDOR: &'static str = "royalmustard";
@@ -211,75 +767,317 @@ impl Plugin for Prismatine {
         // Resize buffers and perform other potentially expensive initialization operations here.
         // The `reset()` function is always called right after this function. You can remove this
         // function if you do not need it.
-        context.set_latency_samples(WINDOW_SIZE as u32);
+        let fft_window_size = self.params.fft_window_size();
+        let linear_phase_eq = self.params.post_eq.value()
+            && self.params.post_eq_mode.value() == PostFilterMode::LinearPhase;
+        let fft_path = self.params.remove_dc.value() || self.params.freeze.value() || linear_phase_eq;
+        self.latency_samples = Self::latency_samples(fft_window_size, fft_path, linear_phase_eq);
+        context.set_latency_samples(self.latency_samples);
+        self.sample_rate = buffer_config.sample_rate;
+        self.peak_meter_decay_weight = 0.25f64
+            .powf((buffer_config.sample_rate as f64 * PEAK_METER_DECAY_MS / 1000.0).recip())
+            as f32;
+        self.input_level.set_sample_rate(buffer_config.sample_rate);
+        self.metering.sample_rate.store(buffer_config.sample_rate, Ordering::Relaxed);
+        self.output_level.set_sample_rate(buffer_config.sample_rate);
         self.filter_spectrum =
-            kinetic_spectrum_from_window_size(WINDOW_SIZE, buffer_config.sample_rate);
-        //nih_dbg!(&self.filter_spectrum);
-        nih_util::window::hann_in_place(&mut self.window_buff);
-        nih_dbg!(self.filter_spectrum.iter().map(|c| c.abs()).sum::<f32>());
-        //nih_dbg!(self.scratch_buffer);
-        //self.prev = vec![0.0, 0.0]; //Two input channels, as specified in the layout, idk if this needs to by dynamic
+            kinetic_spectrum_from_window_size(fft_window_size, buffer_config.sample_rate);
+
+        // Plan every selectable FFT size up front so the FFT size parameter can be changed while
+        // processing without allocating on the audio thread
+        let mut planner = RealFftPlanner::new();
+        self.r2c_plans = (MIN_FFT_ORDER..=MAX_FFT_ORDER)
+            .map(|order| planner.plan_fft_forward(1 << order))
+            .collect();
+        self.c2r_plans = (MIN_FFT_ORDER..=MAX_FFT_ORDER)
+            .map(|order| planner.plan_fft_inverse(1 << order))
+            .collect();
+        let scratch_len = self
+            .r2c_plans
+            .iter()
+            .map(|plan| plan.get_scratch_len())
+            .chain(self.c2r_plans.iter().map(|plan| plan.get_scratch_len()))
+            .max()
+            .unwrap_or(0);
+        self.scratch_buffer = vec![Complex32::new(0.0, 0.0); scratch_len];
+        self.complex_fft_buffer = vec![Complex32::new(0.0, 0.0); MAX_FFT_WINDOW_SIZE / 2 + 1];
+        self.window_buff = vec![0.0; MAX_FFT_WINDOW_SIZE];
+
+        let num_channels = audio_io_layout
+            .main_input_channels
+            .map(NonZeroU32::get)
+            .unwrap_or(2) as usize;
+        self.stft = FFTHelper::new(num_channels, MAX_FFT_WINDOW_SIZE);
+        self.feedback = Feedback::new(num_channels, buffer_config.sample_rate);
+        self.freeze = SpectralFreeze::new(num_channels, MAX_FFT_WINDOW_SIZE);
+        self.linear_phase = LinearPhaseFilter::new(num_channels, MAX_FFT_WINDOW_SIZE);
+        self.set_fft_window_size(fft_window_size);
+
+        // The wavetable may have been replaced when the plugin's state was restored
+        let wavetable = self.params.cpr_wavetable.read().unwrap();
+        self.cpr.set_wavetable(&wavetable);
+        self.morph_cpr.set_wavetable(&wavetable);
+        drop(wavetable);
+        self.params.cpr_wavetable_changed.store(false, Ordering::Release);
+
+        // Restored morph targets still need to be resolved to the parameters
+        let mut morph_targets = self.params.morph_targets.write().unwrap();
+        morph_targets.rebuild_table(&self.params);
+        self.morph.set_targets(morph_targets.table());
+        drop(morph_targets);
+        self.params.morph_targets_changed.store(false, Ordering::Release);
         true
     }
 
     fn reset(&mut self) {
-        //self.stft.set_block_size(WINDOW_SIZE);
         self.stft.reset();
+        self.freeze.reset();
+        self.post_filter.reset();
+        self.linear_phase.reset();
+        self.emphasis.reset();
+        self.bands.reset();
+        self.morph_bands.reset();
+        self.morph_crossfading = false;
+        self.feedback.reset();
+        self.sync_block = None;
         for afloat in self.phase.as_ref()
         {
             afloat.store(0.0, std::sync::atomic::Ordering::Release);
         }
-        self.prev = [0.0;2];
+        self.phase_history.iter().for_each(AtomicRingBuffer::clear);
+        self.input_level.reset();
+        self.output_level.reset();
+        self.phase_history_counter = 0;
+        for (revolutions, voltage) in self.revolutions.iter().zip(self.voltage.iter())
+        {
+            revolutions.store(0, Ordering::Release);
+            voltage.store(0.0, Ordering::Release);
+        }
     }
 
     fn process(
         &mut self,
         buffer: &mut Buffer,
         _aux: &mut AuxiliaryBuffers,
-        _context: &mut impl ProcessContext<Self>,
+        context: &mut impl ProcessContext<Self>,
     ) -> ProcessStatus {
-        
-        //TODO: Play with simd
-        for channel_samples in buffer.iter_samples() {
-            for (i, sample) in channel_samples.into_iter().enumerate() {
-                if *sample == 0.0{ //dont process silence
-                    continue;
-                }
-                let diff = self.prev[i] - *sample;
-                let dphi = 
-                if self.params.invert_phase.value()
-                {
-                   util::map_range_linear(1.0/(self.prev[i] - *sample), 0.0, 1.0/f32::EPSILON, 0.0, 1.0) * self.params.phase_gain.smoothed.next()
-                }
-                else {
-                    (self.prev[i] - *sample) * self.params.phase_gain.smoothed.next()
-                };
-                
-                self.prev[i] = *sample;
-                //prevent NaN poisoning
-                if self.prev[i].is_nan()
-                {
-                    self.prev[i] = 0.0;
+        // The buffers were allocated for the largest FFT size in `initialize()`, so this doesn't
+        // allocate
+        let fft_window_size = self.params.fft_window_size();
+        if fft_window_size != self.fft_window_size {
+            self.set_fft_window_size(fft_window_size);
+        }
+
+
+        if self.params.cpr_wavetable_changed.swap(false, Ordering::AcqRel)
+        {
+            // Don't wait for the editor, try again on the next buffer instead
+            match self.params.cpr_wavetable.try_read()
+            {
+                Ok(points) => {
+                    self.cpr.set_wavetable(&points);
+                    self.morph_cpr.set_wavetable(&points);
                 }
+                Err(_) => self.params.cpr_wavetable_changed.store(true, Ordering::Release),
+            }
+        }
+        if self.params.morph_targets_changed.swap(false, Ordering::AcqRel)
+        {
+            match self.params.morph_targets.try_read()
+            {
+                Ok(targets) => self.morph.set_targets(targets.table()),
+                Err(_) => self.params.morph_targets_changed.store(true, Ordering::Release),
+            }
+        }
+        // Discrete parameters are read once per buffer, at the morph position the buffer starts at
+        self.morph.set_amount(self.params.morph.value());
+        // The table is only rebuilt once per buffer, so the smoother is stepped over the whole
+        // buffer at once
+        let cpr_amount = self.morph.float_step(&self.params.cpr_amount, buffer.samples() as u32);
+        let cpr_shape = self.morph.discrete_at(&self.params.cpr_shape, false);
+        let invert_phase = self.morph.discrete_at(&self.params.invert_phase, false);
+        let quantize_steps = self.morph.discrete_at(&self.params.phase_steps, false) as u32;
+        let layout = self.band_layout(false);
+        self.cpr.update(cpr_shape, cpr_amount);
+
+        // The band layout and the junction settings can't be interpolated, so they're crossfaded
+        // instead by running a second set of bands with the B settings
+        let morph_cpr_shape = self.morph.discrete_at(&self.params.cpr_shape, true);
+        let morph_invert_phase = self.morph.discrete_at(&self.params.invert_phase, true);
+        let morph_quantize_steps = self.morph.discrete_at(&self.params.phase_steps, true) as u32;
+        let morph_layout = self.band_layout(true);
+        let morph_crossfade = morph_cpr_shape != cpr_shape
+            || morph_invert_phase != invert_phase
+            || morph_quantize_steps != quantize_steps
+            || morph_layout != layout;
+        if morph_crossfade
+        {
+            self.morph_cpr.update(morph_cpr_shape, cpr_amount);
+            if !self.morph_crossfading
+            {
+                // Start out from the same state so the crossfade doesn't start with a jump
+                self.morph_bands.clone_from(&self.bands);
+            }
+        }
+        self.morph_crossfading = morph_crossfade;
 
-                let mut local_phase = self.phase[i].load(std::sync::atomic::Ordering::Acquire);
-                //limit maximum phase for numerical precision
-                if local_phase + dphi > MAX_PHASE {
-                    local_phase += -MAX_PHASE + dphi;
-                    
-                } else if local_phase + dphi < -MAX_PHASE {
-                    local_phase += MAX_PHASE + dphi;
-                   
-                } else {
-                    local_phase += dphi;
+        //TODO: Play with simd
+        // The minimum phase post EQ is crossfaded when it's only on at one end of the morph. The
+        // linear phase post EQ and the mode itself switch over halfway through
+        let post_eq_mode = self.morph.discrete(&self.params.post_eq_mode);
+        let minimum_phase_eq = post_eq_mode == PostFilterMode::MinimumPhase;
+        let linear_phase_eq = post_eq_mode == PostFilterMode::LinearPhase && self.morph.discrete(&self.params.post_eq);
+        // FFT yeet DC component, or hold the spectrum. The DC removal is crossfaded when it's only on
+        // at one end of the morph, freeze switches over halfway through
+        let dc_removal = self.morph.crossfaded(&self.params.remove_dc);
+        let remove_dc = dc_removal > 0.0;
+        let freeze = self.morph.discrete(&self.params.freeze);
+        if !freeze
+        {
+            self.freeze.release();
+        }
+        // A released freeze still needs a block to fade back to the input
+        let fft_path = remove_dc || freeze || linear_phase_eq || self.freeze.is_releasing();
+        let latency_samples = Self::latency_samples(fft_window_size, fft_path, linear_phase_eq);
+        if latency_samples != self.latency_samples
+        {
+            if self.latency_samples == 0
+            {
+                // The FFT path starts up again, the blocks left over from the last time it ran are
+                // stale
+                self.stft.reset();
+            }
+            self.latency_samples = latency_samples;
+            context.set_latency_samples(latency_samples);
+        }
+        let mut band_peaks = [0.0f32; MAX_BANDS];
+        // The B side of the crossfade isn't metered
+        let mut morph_band_peaks = [0.0f32; MAX_BANDS];
+        let clock = TransportClock::from_transport(context.transport(), self.sample_rate);
+        // Phase sync only acts at the division boundaries, so it switches over halfway through
+        let phase_sync = self.morph.discrete(&self.params.phase_sync);
+        // The LFOs at both ends run side by side and are crossfaded
+        let lfo_rate = self.morph.discrete_at(&self.params.lfo_rate, false);
+        let morph_lfo_rate = self.morph.discrete_at(&self.params.lfo_rate, true);
+        let editor_open = self.params.editor_state.is_open();
+        for (sample_idx, mut channel_samples) in buffer.iter_samples().enumerate() {
+            if editor_open
+            {
+                let num_channels = channel_samples.len();
+                let mono = channel_samples.iter_mut().map(|sample| *sample).sum::<f32>() / num_channels as f32;
+                self.input_level.process(mono);
+                self.metering.input_scope.push(mono);
+            }
+            self.morph.set_amount(self.params.morph.smoothed.next());
+            let morph_amount = self.morph.amount();
+            self.emphasis.update(
+                self.sample_rate,
+                self.morph.float(&self.params.emphasis_freq),
+                self.morph.float(&self.params.emphasis_amount),
+            );
+            let post_eq_mix = if minimum_phase_eq { self.morph.crossfaded(&self.params.post_eq) } else { 0.0 };
+            if post_eq_mix > 0.0
+            {
+                self.post_filter.update(self.sample_rate, PostFilterSettings {
+                    low_cut: self.morph.float(&self.params.low_cut),
+                    high_cut: self.morph.float(&self.params.high_cut),
+                    tilt: self.morph.float(&self.params.tilt),
+                });
+            }
+            let crossover_frequencies = [
+                self.morph.float(&self.params.crossover_1),
+                self.morph.float(&self.params.crossover_2),
+                self.morph.float(&self.params.crossover_3),
+            ];
+            self.bands.crossover.update(self.sample_rate, crossover_frequencies);
+            if morph_crossfade
+            {
+                self.morph_bands.crossover.update(self.sample_rate, crossover_frequencies);
+            }
+            let quantize_hysteresis = self.morph.float_unsmoothed(&self.params.phase_hysteresis);
+            let mut phase_offset = self.morph.float(&self.params.phase_offset);
+            let lfo_depth = self.morph.float(&self.params.lfo_depth);
+            match &clock
+            {
+                Some(clock) => {
+                    let beat = clock.beat_at(sample_idx);
+                    if let Some(length) = phase_sync.length_beats(clock.beats_per_bar)
+                    {
+                        let block = (beat / length).floor() as i64;
+                        if self.sync_block.is_some_and(|last| last != block)
+                        {
+                            for junction in self.bands.junctions.iter_mut().chain(self.morph_bands.junctions.iter_mut()).flatten()
+                            {
+                                junction.reset_phase();
+                            }
+                        }
+                        self.sync_block = Some(block);
+                    }
+                    else {
+                        self.sync_block = None;
+                    }
+                    let lfo_at = |rate: SyncDivision| {
+                        rate.length_beats(clock.beats_per_bar)
+                            .map_or(0.0, |length| ((beat / length).fract() as f32 * f32::consts::TAU).sin())
+                    };
+                    let (lfo, morph_lfo) = (lfo_at(lfo_rate), lfo_at(morph_lfo_rate));
+                    phase_offset += lfo_depth * (lfo + (morph_lfo - lfo) * morph_amount);
                 }
-                self.phase[i].store(local_phase, std::sync::atomic::Ordering::Release);
-                if self.params.invert_phase.value()
+                None => self.sync_block = None,
+            }
+            let phase_offset = phase_offset.to_radians();
+            let band_gains: [(f32, f32); MAX_BANDS] = std::array::from_fn(|band| {
+                let (phase_gain, critical_current) = self.params.band_junction_params(band);
+                (self.morph.float(phase_gain), self.morph.float(critical_current))
+            });
+            let band_settings: [JunctionSettings; MAX_BANDS] = std::array::from_fn(|band| JunctionSettings {
+                phase_gain: band_gains[band].0,
+                critical_current: band_gains[band].1,
+                invert_phase,
+                quantize_steps,
+                quantize_hysteresis,
+                phase_offset,
+                cpr: &self.cpr,
+            });
+            let morph_band_settings: [JunctionSettings; MAX_BANDS] = std::array::from_fn(|band| JunctionSettings {
+                invert_phase: morph_invert_phase,
+                quantize_steps: morph_quantize_steps,
+                cpr: &self.morph_cpr,
+                ..band_settings[band]
+            });
+
+            let feedback = self.morph.float(&self.params.feedback);
+            let feedback_delay = self.morph.float(&self.params.feedback_delay) * self.sample_rate / 1000.0;
+
+            for (i, sample) in channel_samples.into_iter().enumerate() {
+                //dont run the junctions on silence, unless the feedback can keep them going. The
+                //filters around them keep running so their tails aren't cut off
+                let silent = *sample == 0.0 && feedback == 0.0;
+                let input = self.emphasis.pre(i, *sample) + feedback * self.feedback.read(i, feedback_delay);
+
+                *sample = self.bands.process(i, input, silent, &layout, &band_settings, &mut band_peaks);
+                if morph_crossfade
                 {
-                   *sample = diff * self.params.I_c.smoothed.next() * local_phase.sin();
+                    let morph_out = self.morph_bands.process(
+                        i,
+                        input,
+                        silent,
+                        &morph_layout,
+                        &morph_band_settings,
+                        &mut morph_band_peaks,
+                    );
+                    *sample += (morph_out - *sample) * morph_amount;
                 }
-                else {
-                    *sample = self.params.I_c.smoothed.next() * local_phase.sin();
+                self.feedback.write(i, *sample);
+                let junction = &self.bands.junctions[i][0];
+                self.phase[i].store(junction.phase(), std::sync::atomic::Ordering::Release);
+                self.revolutions[i].store(junction.revolutions(), Ordering::Release);
+                self.voltage[i].store(junction.dphi() * self.sample_rate, Ordering::Release);
+                *sample = self.emphasis.de(i, *sample);
+                if post_eq_mix > 0.0
+                {
+                    let filtered = self.post_filter.process(i, *sample);
+                    *sample += (filtered - *sample) * post_eq_mix;
                 }
                 if sample.is_nan()
                 {
@@ -288,35 +1086,114 @@ impl Plugin for Prismatine {
                 //nih_dbg!(&sample);
 
                 
+            }
+            self.feedback.advance();
+
+            self.phase_history_counter += 1;
+            if self.phase_history_counter >= PHASE_HISTORY_DECIMATION
+            {
+                self.phase_history_counter = 0;
+                for (history, channel) in self.phase_history.iter().zip(self.bands.junctions.iter())
+                {
+                    history.push(channel[0].phase());
+                }
             }
             //TODO: Reset phase when input stops to prevent outputting constant signal
             //reset phase buttons in GUI
             
         }
-        // FFT yeet DC component
-        if self.params.remove_dc.value()
+        let meter_decay = self.peak_meter_decay_weight.powi(buffer.samples() as i32);
+        if editor_open
         {
-            self.stft.process(buffer, |_channel_idx, real_fft_buffer| {
-            self.r2c_plan
-                .process_with_scratch(
-                    real_fft_buffer,
-                    &mut self.complex_fft_buffer,
-                    &mut self.scratch_buffer,
-                )
-                .unwrap();
-
-            self.complex_fft_buffer[0] = 0.0.into();
-            self.complex_fft_buffer.iter_mut().for_each(|c| *c *= GAIN_COMPENSATION);
-            self.c2r_plan
-                .process_with_scratch(
-                    &mut self.complex_fft_buffer,
+            for (meter, peak) in self.band_meters.iter().zip(band_peaks)
+            {
+                let old = meter.load(std::sync::atomic::Ordering::Relaxed);
+                meter.store(peak.max(old * meter_decay), std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        let plan_idx = (fft_window_size.trailing_zeros() as i32 - MIN_FFT_ORDER) as usize;
+        let r2c_plan = &self.r2c_plans[plan_idx];
+        let c2r_plan = &self.c2r_plans[plan_idx];
+        let mut fft = FftPlans {
+            r2c: r2c_plan.as_ref(),
+            c2r: c2r_plan.as_ref(),
+            scratch: &mut self.scratch_buffer,
+        };
+        if linear_phase_eq
+        {
+            // The kernel is redesigned at most once per buffer, so the smoothers are stepped over
+            // the whole buffer at once
+            let steps = buffer.samples() as u32;
+            self.post_filter.update(self.sample_rate, PostFilterSettings {
+                low_cut: self.morph.float_step(&self.params.low_cut, steps),
+                high_cut: self.morph.float_step(&self.params.high_cut, steps),
+                tilt: self.morph.float_step(&self.params.tilt, steps),
+            });
+            self.linear_phase.design(&self.post_filter, fft_window_size, &mut fft);
+        }
+        else {
+            self.linear_phase.reset();
+        }
+        if fft_path
+        {
+            let complex_fft_buffer = &mut self.complex_fft_buffer[..fft_window_size / 2 + 1];
+            let window = &self.window_buff[..fft_window_size];
+            let spectral_freeze = &mut self.freeze;
+            let linear_phase = &mut self.linear_phase;
+            let freeze_phase = self.morph.discrete(&self.params.freeze_phase);
+            let gain_compensation = 1.0 / fft_window_size as f32;
+
+            self.stft.process(buffer, |channel_idx, real_fft_buffer| {
+            if freeze && spectral_freeze.is_captured(channel_idx)
+            {
+                spectral_freeze.resynthesize(
+                    channel_idx,
+                    freeze_phase,
+                    window,
+                    complex_fft_buffer,
+                    &mut fft,
                     real_fft_buffer,
-                    &mut self.scratch_buffer,
-                )
-                .unwrap();
+                );
+            }
+            else
+            {
+                if remove_dc || freeze
+                {
+                    fft.forward(real_fft_buffer, complex_fft_buffer);
+                    if remove_dc
+                    {
+                        complex_fft_buffer[0] *= 1.0 - dc_removal;
+                    }
+                    complex_fft_buffer.iter_mut().for_each(|c| *c *= gain_compensation);
+                    if freeze
+                    {
+                        spectral_freeze.capture(channel_idx, complex_fft_buffer);
+                    }
+                    fft.inverse(complex_fft_buffer, real_fft_buffer);
+                }
+                spectral_freeze.fade_out(channel_idx, window, real_fft_buffer);
+            }
+
+            if linear_phase_eq
+            {
+                linear_phase.process(channel_idx, real_fft_buffer, complex_fft_buffer, &mut fft);
+            }
             });
         }
         
+        if editor_open
+        {
+            self.metering.scope_delay.store(self.latency_samples as usize, Ordering::Relaxed);
+            self.input_level.publish(&self.metering.input, meter_decay);
+            for mut channel_samples in buffer.iter_samples()
+            {
+                let num_channels = channel_samples.len();
+                let mono = channel_samples.iter_mut().map(|sample| *sample).sum::<f32>() / num_channels as f32;
+                self.output_level.process(mono);
+                self.metering.output_scope.push(mono);
+            }
+            self.output_level.publish(&self.metering.output, meter_decay);
+        }
 
         ProcessStatus::Normal
     }
@@ -326,6 +1203,11 @@ impl Plugin for Prismatine {
             PrismatineEditorParams{
                 prismatine_params: self.params.clone(),
                 phase: self.phase.clone(),
+                phase_history: self.phase_history.clone(),
+                revolutions: self.revolutions.clone(),
+                voltage: self.voltage.clone(),
+                band_meters: self.band_meters.clone(),
+                metering: self.metering.clone(),
             },
             self.params.editor_state.clone(),
         )
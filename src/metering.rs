@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use nih_plug::prelude::AtomicF32;
+
+use crate::ring_buffer::AtomicRingBuffer;
+use crate::MAX_LATENCY;
+
+/// The number of samples shown by the oscilloscope and analyzed by the spectrum analyzer. The
+/// oscilloscope looks for a trigger in all but the last quarter and shows the samples after it.
+pub const SCOPE_SIZE: usize = 2048;
+/// The number of samples kept in the scope buffers. The input is read further back to line it up
+/// with the output, which is delayed by the plugin's latency.
+pub const SCOPE_HISTORY_SIZE: usize = SCOPE_SIZE + MAX_LATENCY;
+/// The RMS integration time.
+const RMS_WINDOW_MS: f32 = 300.0;
+
+/// Peak and RMS levels shared with the editor, as linear gain.
+#[derive(Default)]
+pub struct LevelMeter {
+    pub peak: AtomicF32,
+    pub rms: AtomicF32,
+}
+
+/// Everything the editor's meters and oscilloscope need from the audio thread.
+#[derive(Default)]
+pub struct Metering {
+    pub input: LevelMeter,
+    pub output: LevelMeter,
+    /// The input and the wet output, summed to mono.
+    pub input_scope: AtomicRingBuffer<SCOPE_HISTORY_SIZE>,
+    pub output_scope: AtomicRingBuffer<SCOPE_HISTORY_SIZE>,
+    /// How many samples the output lags behind the input, for lining up the scopes.
+    pub scope_delay: AtomicUsize,
+    /// The sample rate the scope buffers were recorded at.
+    pub sample_rate: AtomicF32,
+}
+
+/// The audio thread side of a [`LevelMeter`]. Feed it samples and publish once per buffer.
+pub struct LevelFollower {
+    peak: f32,
+    mean_square: f32,
+    /// The one pole coefficient for the mean square.
+    rms_coefficient: f32,
+}
+
+impl Default for LevelFollower {
+    fn default() -> Self {
+        Self {
+            peak: 0.0,
+            mean_square: 0.0,
+            rms_coefficient: 1.0,
+        }
+    }
+}
+
+impl LevelFollower {
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        self.rms_coefficient = 1.0 - (-1.0 / (sample_rate * RMS_WINDOW_MS / 1000.0)).exp();
+    }
+
+    pub fn process(&mut self, sample: f32) {
+        self.peak = self.peak.max(sample.abs());
+        self.mean_square += self.rms_coefficient * (sample * sample - self.mean_square);
+    }
+
+    /// Store the levels of the last buffer in `meter`. The peak falls back by `peak_decay` if the
+    /// buffer's peak is lower than what's displayed.
+    pub fn publish(&mut self, meter: &LevelMeter, peak_decay: f32) {
+        let old_peak = meter.peak.load(Ordering::Relaxed);
+        meter.peak.store(self.peak.max(old_peak * peak_decay), Ordering::Relaxed);
+        meter.rms.store(self.mean_square.sqrt(), Ordering::Relaxed);
+        self.peak = 0.0;
+    }
+
+    pub fn reset(&mut self) {
+        self.peak = 0.0;
+        self.mean_square = 0.0;
+    }
+}
@@ -0,0 +1,327 @@
+use nih_plug::prelude::{BoolParam, FloatParam, Param, ParamPtr, Params};
+use serde::{Deserialize, Serialize};
+
+use crate::presets::Preset;
+use crate::PrismatineParams;
+
+/// Parameters that aren't morphed. The morph itself, and the FFT size, which would change the
+/// latency halfway through.
+const UNMORPHED_PARAM_IDS: [&str; 2] = ["morph", "fft_order"];
+/// Discrete parameters that switch over halfway through the morph instead of being crossfaded, see
+/// [`Morph`]. The post EQ switch is only crossfaded in the minimum phase mode, it's counted as
+/// crossfaded here.
+const SWITCHED_PARAM_IDS: [&str; 4] = ["post_eq_mode", "phase_sync", "freeze", "freeze_phase"];
+
+/// A parameter's normalized values at both ends of the morph.
+#[derive(Debug, Clone, Copy)]
+pub struct MorphTarget {
+    param: ParamPtr,
+    a: f32,
+    b: f32,
+    /// Whether a discrete parameter switches over halfway through instead of being crossfaded.
+    switched: bool,
+}
+
+impl MorphTarget {
+    /// The normalized value at `amount` between the two ends.
+    fn interpolated(&self, amount: f32) -> f32 {
+        self.a + (self.b - self.a) * amount
+    }
+}
+
+/// Two to the power of this is the number of slots in a [`MorphTable`].
+const SLOT_BITS: u32 = 8;
+/// The number of slots in a [`MorphTable`], well above the number of parameters so a lookup
+/// rarely needs to look past the first slot.
+const NUM_SLOTS: usize = 1 << SLOT_BITS;
+
+/// The morph targets by parameter, so the audio thread can look up any parameter's target in
+/// constant time. A target is stored in the slot its parameter's address hashes to, or in the next
+/// free slot after that.
+#[derive(Clone, Copy)]
+pub struct MorphTable {
+    slots: [Option<MorphTarget>; NUM_SLOTS],
+    len: usize,
+}
+
+impl Default for MorphTable {
+    fn default() -> Self {
+        Self {
+            slots: [None; NUM_SLOTS],
+            len: 0,
+        }
+    }
+}
+
+impl MorphTable {
+    fn get(&self, param: ParamPtr) -> Option<&MorphTarget> {
+        let mut slot = home_slot(param);
+        // There's always a free slot, so this ends
+        while let Some(target) = &self.slots[slot] {
+            if target.param == param {
+                return Some(target);
+            }
+            slot = (slot + 1) % NUM_SLOTS;
+        }
+
+        None
+    }
+
+    fn insert(&mut self, target: MorphTarget) {
+        if self.len >= NUM_SLOTS / 2 {
+            nih_plug::nih_debug_assert_failure!("The morph table is full");
+            return;
+        }
+
+        let mut slot = home_slot(target.param);
+        while self.slots[slot].is_some() {
+            slot = (slot + 1) % NUM_SLOTS;
+        }
+        self.slots[slot] = Some(target);
+        self.len += 1;
+    }
+
+    fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+/// The slot a parameter's target is stored in when there's no collision.
+fn home_slot(param: ParamPtr) -> usize {
+    let address = match param {
+        ParamPtr::FloatParam(p) => p as usize,
+        ParamPtr::IntParam(p) => p as usize,
+        ParamPtr::BoolParam(p) => p as usize,
+        ParamPtr::EnumParam(p) => p as usize,
+    };
+    // Fibonacci hashing, which spreads out the parameters even though they're only a few bytes
+    // apart in the same struct
+    ((address as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (u64::BITS - SLOT_BITS)) as usize
+}
+
+/// What the morph sets a parameter to at some position, for showing it in the editor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MorphedValue {
+    /// A single normalized value.
+    Value(f32),
+    /// A discrete parameter that's crossfaded between its normalized values at the two ends.
+    Crossfade(f32, f32),
+}
+
+/// The end the morph is at, `false` for A and `true` for B, or `None` while it's in between.
+pub fn end_at(amount: f32) -> Option<bool> {
+    if amount <= 0.0 {
+        Some(false)
+    } else if amount >= 1.0 {
+        Some(true)
+    } else {
+        None
+    }
+}
+
+/// The two parameter sets the `morph` parameter sweeps between, stored from the editor and saved
+/// with the plugin's state.
+#[derive(Default, Serialize, Deserialize)]
+pub struct MorphTargets {
+    pub a: Option<Preset>,
+    pub b: Option<Preset>,
+    /// Both sets resolved to normalized values for every parameter that differs between them, for
+    /// the audio thread. Empty unless both ends are stored.
+    #[serde(skip)]
+    table: MorphTable,
+}
+
+impl MorphTargets {
+    /// Store captured settings as one end of the morph, with the morphed parameters at the values
+    /// heard at `amount` instead of their hidden control values. Storing is only done at either end
+    /// of the morph, since the crossfaded parameters have no single value in between. Only the
+    /// parameter values are kept.
+    pub fn store(&mut self, mut preset: Preset, b: bool, amount: f32, params: &PrismatineParams) {
+        preset.fields.clear();
+        for (id, param, _) in params.param_map() {
+            if let Some(MorphedValue::Value(normalized)) = self.value(param, amount) {
+                // SAFETY: The pointers come from `params`, which outlives this loop
+                preset.values.insert(id, unsafe { param.preview_plain(normalized) });
+            }
+        }
+        if b {
+            self.b = Some(preset);
+        } else {
+            self.a = Some(preset);
+        }
+        self.rebuild_table(params);
+    }
+
+    /// Set one parameter's normalized value at the A or B end. Returns whether the parameter is
+    /// still morphed, it isn't once both ends agree.
+    pub fn set_end_value(&mut self, param: ParamPtr, b: bool, normalized: f32, params: &PrismatineParams) -> bool {
+        let end = if b { &mut self.b } else { &mut self.a };
+        let Some(preset) = end else {
+            return false;
+        };
+        if let Some((id, _, _)) = params.param_map().into_iter().find(|(_, ptr, _)| *ptr == param) {
+            // SAFETY: The pointer comes from `params`
+            preset.values.insert(id, unsafe { param.preview_plain(normalized) });
+        }
+        self.rebuild_table(params);
+        self.table.get(param).is_some()
+    }
+
+    /// Whether both ends are stored and differ in at least one parameter.
+    pub fn is_morphing(&self) -> bool {
+        !self.table.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.a = None;
+        self.b = None;
+        self.table.clear();
+    }
+
+    /// Resolve the stored sets for the audio thread. Needs to be called again after the targets
+    /// have been deserialized. Parameters that are the same in both sets aren't morphed, so they
+    /// keep following the controls and the host's automation.
+    pub fn rebuild_table(&mut self, params: &PrismatineParams) {
+        self.table.clear();
+        let (Some(a), Some(b)) = (&self.a, &self.b) else {
+            return;
+        };
+
+        // Both are in the same order as the parameter map
+        let values = a.normalized_values(params).into_iter().zip(b.normalized_values(params));
+        for ((id, _, _), ((param, a), (_, b))) in params.param_map().into_iter().zip(values) {
+            if a != b && !UNMORPHED_PARAM_IDS.contains(&id.as_str()) {
+                let switched = SWITCHED_PARAM_IDS.contains(&id.as_str());
+                self.table.insert(MorphTarget { param, a, b, switched });
+            }
+        }
+    }
+
+    pub fn table(&self) -> &MorphTable {
+        &self.table
+    }
+
+    /// What the morph sets `param` to at `amount`, or `None` if the parameter isn't morphed.
+    /// Between the ends, the discrete parameters that switch halfway through have the nearer end's
+    /// value and the rest are crossfaded.
+    pub fn value(&self, param: ParamPtr, amount: f32) -> Option<MorphedValue> {
+        self.table.get(param).map(|target| match (param, end_at(amount)) {
+            (ParamPtr::FloatParam(_), _) => MorphedValue::Value(target.interpolated(amount)),
+            (_, Some(b)) => MorphedValue::Value(if b { target.b } else { target.a }),
+            _ if target.switched => MorphedValue::Value(if amount >= 0.5 { target.b } else { target.a }),
+            _ => MorphedValue::Crossfade(target.a, target.b),
+        })
+    }
+}
+
+/// The audio thread's copy of the morph targets, with the current morph position.
+///
+/// Float parameters are interpolated. The discrete parameters that change the junctions and bands
+/// are crossfaded by running both ends, and the post EQ (in its minimum phase mode), the LFO rate
+/// and the DC removal are crossfaded as well. The post EQ mode, the linear phase post EQ, phase
+/// sync, freeze and the freeze phase switch over halfway through instead.
+#[derive(Default)]
+pub struct Morph {
+    table: MorphTable,
+    amount: f32,
+}
+
+impl Morph {
+    pub fn set_targets(&mut self, table: &MorphTable) {
+        self.table = *table;
+    }
+
+    /// The position between the two sets, from 0 for A to 1 for B.
+    pub fn amount(&self) -> f32 {
+        self.amount
+    }
+
+    pub fn set_amount(&mut self, amount: f32) {
+        self.amount = amount;
+    }
+
+    /// The normalized value between the two sets at the current position.
+    fn interpolated(&self, param: ParamPtr) -> Option<f32> {
+        self.table.get(param).map(|target| target.interpolated(self.amount))
+    }
+
+    /// The normalized value at the A or B end.
+    fn endpoint(&self, param: ParamPtr, b: bool) -> Option<f32> {
+        self.table.get(param).map(|target| if b { target.b } else { target.a })
+    }
+
+    /// The smoothed value of a float parameter, or the value interpolated in normalized units if
+    /// the parameter is morphed. The smoother is advanced either way so it doesn't jump when the
+    /// morph is turned off.
+    pub fn float(&self, param: &FloatParam) -> f32 {
+        let value = param.smoothed.next();
+        match self.interpolated(param.as_ptr()) {
+            Some(normalized) => param.preview_plain(normalized),
+            None => value,
+        }
+    }
+
+    /// [`Morph::float()`] for a value that's only needed once every `steps` samples.
+    pub fn float_step(&self, param: &FloatParam, steps: u32) -> f32 {
+        let value = param.smoothed.next_step(steps);
+        match self.interpolated(param.as_ptr()) {
+            Some(normalized) => param.preview_plain(normalized),
+            None => value,
+        }
+    }
+
+    /// [`Morph::float()`] for parameters that aren't smoothed.
+    pub fn float_unsmoothed(&self, param: &FloatParam) -> f32 {
+        match self.interpolated(param.as_ptr()) {
+            Some(normalized) => param.preview_plain(normalized),
+            None => param.value(),
+        }
+    }
+
+    /// The value of a discrete parameter, which switches over halfway through the morph.
+    pub fn discrete<P: Param>(&self, param: &P) -> P::Plain {
+        self.discrete_at(param, self.amount >= 0.5)
+    }
+
+    /// The value of a discrete parameter at the A or B end, or its current value if it isn't
+    /// morphed.
+    pub fn discrete_at<P: Param>(&self, param: &P, b: bool) -> P::Plain {
+        match self.endpoint(param.as_ptr(), b) {
+            Some(normalized) => param.preview_plain(normalized),
+            None => param.modulated_plain_value(),
+        }
+    }
+
+    /// How far a switch is turned on at the current position, for crossfading between its states
+    /// at both ends.
+    pub fn crossfaded(&self, param: &BoolParam) -> f32 {
+        let a = f32::from(self.discrete_at(param, false));
+        let b = f32::from(self.discrete_at(param, true));
+        a + (b - a) * self.amount
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_target_is_found() {
+        let params = PrismatineParams::default();
+        let param_map = params.param_map();
+        // Leave out the first parameter to check that missing parameters aren't found
+        let mut table = MorphTable::default();
+        for (idx, (_, param, _)) in param_map.iter().enumerate().skip(1) {
+            table.insert(MorphTarget { param: *param, a: 0.0, b: idx as f32, switched: false });
+        }
+
+        assert!(table.get(param_map[0].1).is_none());
+        for (idx, (_, param, _)) in param_map.iter().enumerate().skip(1) {
+            assert_eq!(table.get(*param).map(|target| target.b), Some(idx as f32));
+        }
+    }
+}
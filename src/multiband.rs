@@ -0,0 +1,135 @@
+use nih_plug::prelude::*;
+
+use crate::post_filter::{Biquad, BiquadCoefficients, BUTTERWORTH_Q};
+
+/// The maximum number of bands the input can be split into.
+pub const MAX_BANDS: usize = 4;
+const NUM_SPLITS: usize = MAX_BANDS - 1;
+
+/// The junction settings for bands 2 to 4. Band 1 uses the top level `phase_gain` and `I_c`.
+#[derive(Params)]
+pub struct BandParams {
+    #[id = "phase_gain"]
+    pub phase_gain: FloatParam,
+
+    #[id = "I_c"]
+    pub I_c: FloatParam,
+}
+
+impl Default for BandParams {
+    fn default() -> Self {
+        Self {
+            phase_gain: crate::phase_gain_param(),
+            I_c: crate::critical_current_param(),
+        }
+    }
+}
+
+/// Solo and bypass for a single band.
+#[derive(Params)]
+pub struct BandSwitchParams {
+    #[id = "solo"]
+    pub solo: BoolParam,
+
+    /// Pass the band through without running it through its junction.
+    #[id = "bypass"]
+    pub bypass: BoolParam,
+}
+
+impl Default for BandSwitchParams {
+    fn default() -> Self {
+        Self {
+            solo: BoolParam::new("Solo", false),
+            bypass: BoolParam::new("Bypass", false),
+        }
+    }
+}
+
+/// A Linkwitz-Riley crossover network that splits the input into up to [`MAX_BANDS`] bands. The
+/// bands sum back to an allpassed version of the input.
+#[derive(Clone)]
+pub struct Crossover {
+    /// Fourth order Linkwitz-Riley low- and highpasses for every channel and split point, each
+    /// made of two cascaded Butterworth biquads.
+    lowpass: [[[Biquad; 2]; NUM_SPLITS]; 2],
+    highpass: [[[Biquad; 2]; NUM_SPLITS]; 2],
+    /// Allpasses that keep the lower bands in phase with the higher bands, indexed by channel,
+    /// band and the split point the allpass compensates for.
+    allpass: [[[Biquad; NUM_SPLITS]; NUM_SPLITS]; 2],
+    frequencies: Option<[f32; NUM_SPLITS]>,
+}
+
+impl Default for Crossover {
+    fn default() -> Self {
+        Self {
+            lowpass: [[[Biquad::default(); 2]; NUM_SPLITS]; 2],
+            highpass: [[[Biquad::default(); 2]; NUM_SPLITS]; 2],
+            allpass: [[[Biquad::default(); NUM_SPLITS]; NUM_SPLITS]; 2],
+            frequencies: None,
+        }
+    }
+}
+
+impl Crossover {
+    /// Recompute the filters if the crossover frequencies changed. Frequencies that are lower than
+    /// the previous split point are moved up to it.
+    pub fn update(&mut self, sample_rate: f32, mut frequencies: [f32; NUM_SPLITS]) {
+        for split in 1..NUM_SPLITS {
+            frequencies[split] = frequencies[split].max(frequencies[split - 1]);
+        }
+        if self.frequencies == Some(frequencies) {
+            return;
+        }
+        self.frequencies = Some(frequencies);
+
+        for (split, frequency) in frequencies.into_iter().enumerate() {
+            let lowpass = BiquadCoefficients::lowpass(sample_rate, frequency, BUTTERWORTH_Q);
+            let highpass = BiquadCoefficients::highpass(sample_rate, frequency, BUTTERWORTH_Q);
+            let allpass = BiquadCoefficients::allpass(sample_rate, frequency, BUTTERWORTH_Q);
+            for channel in 0..2 {
+                self.lowpass[channel][split]
+                    .iter_mut()
+                    .for_each(|f| f.set_coefficients(lowpass));
+                self.highpass[channel][split]
+                    .iter_mut()
+                    .for_each(|f| f.set_coefficients(highpass));
+                for band in 0..NUM_SPLITS {
+                    self.allpass[channel][band][split].set_coefficients(allpass);
+                }
+            }
+        }
+    }
+
+    /// Split `input` into `bands.len()` bands. A single band is passed through untouched.
+    pub fn split(&mut self, channel: usize, input: f32, bands: &mut [f32]) {
+        let num_bands = bands.len();
+        let mut rest = input;
+        for split in 0..num_bands - 1 {
+            bands[split] = self.lowpass[channel][split]
+                .iter_mut()
+                .fold(rest, |sample, f| f.process(sample));
+            rest = self.highpass[channel][split]
+                .iter_mut()
+                .fold(rest, |sample, f| f.process(sample));
+        }
+        bands[num_bands - 1] = rest;
+
+        // A band has only gone through the splits up to its own, the splits above it need to be
+        // matched with an allpass so the bands sum back together without notches
+        for (band, sample) in bands.iter_mut().enumerate().take(num_bands.saturating_sub(2)) {
+            for split in band + 1..num_bands - 1 {
+                *sample = self.allpass[channel][band][split].process(*sample);
+            }
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.lowpass
+            .iter_mut()
+            .chain(self.highpass.iter_mut())
+            .flatten()
+            .flatten()
+            .for_each(Biquad::reset);
+        self.allpass.iter_mut().flatten().flatten().for_each(Biquad::reset);
+    }
+}
@@ -0,0 +1,303 @@
+use std::f32::consts::{PI, TAU};
+
+use nih_plug::prelude::Enum;
+use realfft::num_complex::Complex32;
+
+use crate::fft_filter::{FftPlans, OverlapAdd};
+
+/// The frequency the tilt pivots around.
+const TILT_PIVOT_HZ: f32 = 1000.0;
+/// Butterworth Q for the low and high cut.
+pub const BUTTERWORTH_Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// How the post filter is applied to the wet signal.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PostFilterMode {
+    /// Biquads running per sample, no added latency.
+    #[name = "Minimum Phase"]
+    MinimumPhase,
+    /// A linear phase FIR with the biquads' magnitude response, applied in the FFT path. Adds a
+    /// quarter of the FFT size to the DC removal's latency.
+    #[name = "Linear Phase"]
+    LinearPhase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PostFilterSettings {
+    pub low_cut: f32,
+    pub high_cut: f32,
+    /// Tilt in dB, positive values make the highs louder and the lows quieter.
+    pub tilt: f32,
+}
+
+/// RBJ cookbook biquad coefficients, normalized so `a0` is 1.
+#[derive(Debug, Clone, Copy)]
+pub struct BiquadCoefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl BiquadCoefficients {
+    pub const IDENTITY: Self = Self { b0: 1.0, b1: 0.0, b2: 0.0, a1: 0.0, a2: 0.0 };
+
+    fn normalized(b0: f32, b1: f32, b2: f32, a0: f32, a1: f32, a2: f32) -> Self {
+        Self { b0: b0 / a0, b1: b1 / a0, b2: b2 / a0, a1: a1 / a0, a2: a2 / a0 }
+    }
+
+    pub fn lowpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let omega = TAU * frequency.min(sample_rate * 0.49) / sample_rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized((1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    pub fn highpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let omega = TAU * frequency.min(sample_rate * 0.49) / sample_rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized((1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    pub fn allpass(sample_rate: f32, frequency: f32, q: f32) -> Self {
+        let omega = TAU * frequency.min(sample_rate * 0.49) / sample_rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / (2.0 * q);
+        Self::normalized(1.0 - alpha, -2.0 * cos, 1.0 + alpha, 1.0 + alpha, -2.0 * cos, 1.0 - alpha)
+    }
+
+    /// A high shelf with a shelf slope of 1.
+    pub fn high_shelf(sample_rate: f32, frequency: f32, gain_db: f32) -> Self {
+        let a = 10.0f32.powf(gain_db / 40.0);
+        let omega = TAU * frequency.min(sample_rate * 0.49) / sample_rate;
+        let (sin, cos) = omega.sin_cos();
+        let alpha = sin / 2.0 * 2.0f32.sqrt();
+        let two_sqrt_a_alpha = 2.0 * a.sqrt() * alpha;
+        Self::normalized(
+            a * ((a + 1.0) + (a - 1.0) * cos + two_sqrt_a_alpha),
+            -2.0 * a * ((a - 1.0) + (a + 1.0) * cos),
+            a * ((a + 1.0) + (a - 1.0) * cos - two_sqrt_a_alpha),
+            (a + 1.0) - (a - 1.0) * cos + two_sqrt_a_alpha,
+            2.0 * ((a - 1.0) - (a + 1.0) * cos),
+            (a + 1.0) - (a - 1.0) * cos - two_sqrt_a_alpha,
+        )
+    }
+
+    /// The filter's magnitude response at the normalized angular frequency `omega`.
+    pub fn magnitude(&self, omega: f32) -> f32 {
+        let z1 = Complex32::from_polar(1.0, -omega);
+        let z2 = z1 * z1;
+        let numerator = self.b0 + z1 * self.b1 + z2 * self.b2;
+        let denominator = 1.0 + z1 * self.a1 + z2 * self.a2;
+        (numerator / denominator).norm()
+    }
+}
+
+/// A transposed direct form II biquad.
+#[derive(Debug, Clone, Copy)]
+pub struct Biquad {
+    coefficients: BiquadCoefficients,
+    s1: f32,
+    s2: f32,
+}
+
+impl Default for Biquad {
+    fn default() -> Self {
+        Self { coefficients: BiquadCoefficients::IDENTITY, s1: 0.0, s2: 0.0 }
+    }
+}
+
+impl Biquad {
+    pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn process(&mut self, sample: f32) -> f32 {
+        let c = &self.coefficients;
+        let out = c.b0 * sample + self.s1;
+        self.s1 = c.b1 * sample - c.a1 * out + self.s2;
+        self.s2 = c.b2 * sample - c.a2 * out;
+        out
+    }
+
+    pub fn reset(&mut self) {
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+    }
+}
+
+/// Low cut, high cut and tilt applied to the wet signal after the junction.
+pub struct PostFilter {
+    /// Low cut, high cut and tilt shelf for each channel.
+    filters: [[Biquad; 3]; 2],
+    coefficients: [BiquadCoefficients; 3],
+    /// The overall gain that turns the high shelf into a tilt around `TILT_PIVOT_HZ`.
+    tilt_gain: f32,
+    settings: Option<PostFilterSettings>,
+}
+
+impl Default for PostFilter {
+    fn default() -> Self {
+        Self {
+            filters: [[Biquad::default(); 3]; 2],
+            coefficients: [BiquadCoefficients::IDENTITY; 3],
+            tilt_gain: 1.0,
+            settings: None,
+        }
+    }
+}
+
+impl PostFilter {
+    /// Recompute the coefficients if the settings changed since the last call.
+    pub fn update(&mut self, sample_rate: f32, settings: PostFilterSettings) {
+        if self.settings == Some(settings) {
+            return;
+        }
+        self.settings = Some(settings);
+
+        self.coefficients = [
+            BiquadCoefficients::highpass(sample_rate, settings.low_cut, BUTTERWORTH_Q),
+            BiquadCoefficients::lowpass(sample_rate, settings.high_cut, BUTTERWORTH_Q),
+            BiquadCoefficients::high_shelf(sample_rate, TILT_PIVOT_HZ, settings.tilt),
+        ];
+        self.tilt_gain = 10.0f32.powf(-settings.tilt / 40.0);
+        for channel in self.filters.iter_mut() {
+            for (filter, coefficients) in channel.iter_mut().zip(self.coefficients) {
+                filter.set_coefficients(coefficients);
+            }
+        }
+    }
+
+    pub fn process(&mut self, channel: usize, sample: f32) -> f32 {
+        self.filters[channel]
+            .iter_mut()
+            .fold(sample, |sample, filter| filter.process(sample))
+            * self.tilt_gain
+    }
+
+    /// The combined magnitude response for FFT bin `bin` of a `window_size` sample FFT, used to
+    /// design the [`LinearPhaseFilter`].
+    pub fn bin_magnitude(&self, bin: usize, window_size: usize) -> f32 {
+        let omega = TAU * bin as f32 / window_size as f32;
+        debug_assert!(omega <= PI + f32::EPSILON);
+        self.coefficients
+            .iter()
+            .map(|c| c.magnitude(omega))
+            .product::<f32>()
+            * self.tilt_gain
+    }
+
+    pub fn reset(&mut self) {
+        self.filters.iter_mut().flatten().for_each(Biquad::reset);
+    }
+}
+
+/// The post filter's magnitude response as a linear phase FIR, applied to the FFT path's blocks by
+/// zero padded overlap-add. Every block is convolved in two halves, so the kernel can be half a
+/// block long without the circular convolution wrapping around.
+pub struct LinearPhaseFilter {
+    /// The kernel's spectrum, already scaled for the unnormalized FFTs.
+    kernel: Vec<Complex32>,
+    overlap_add: OverlapAdd,
+    segment: Vec<f32>,
+    /// The settings and FFT size the kernel was designed for.
+    designed_for: Option<(PostFilterSettings, usize)>,
+    active: bool,
+}
+
+impl LinearPhaseFilter {
+    pub fn new(channels: usize, max_window_size: usize) -> Self {
+        Self {
+            kernel: vec![Complex32::new(0.0, 0.0); max_window_size / 2 + 1],
+            overlap_add: OverlapAdd::new(channels, max_window_size),
+            segment: vec![0.0; max_window_size],
+            designed_for: None,
+            active: false,
+        }
+    }
+
+    /// The filter's delay for a `window_size` sample FFT, on top of the FFT block's latency.
+    pub const fn latency(window_size: usize) -> usize {
+        window_size / 4
+    }
+
+    /// Design the kernel for `post_filter`'s current response by frequency sampling, if it changed
+    /// since the last call. The zero phase impulse response is delayed by
+    /// [`LinearPhaseFilter::latency()`] and Hann windowed to half a block plus one sample.
+    pub fn design(
+        &mut self,
+        post_filter: &PostFilter,
+        window_size: usize,
+        fft: &mut FftPlans,
+    ) {
+        let Some(settings) = post_filter.settings else {
+            return;
+        };
+        if self.designed_for == Some((settings, window_size)) {
+            return;
+        }
+        self.designed_for = Some((settings, window_size));
+
+        let kernel = &mut self.kernel[..window_size / 2 + 1];
+        let impulse_response = &mut self.segment[..window_size];
+        for (bin, c) in kernel.iter_mut().enumerate() {
+            *c = Complex32::new(post_filter.bin_magnitude(bin, window_size), 0.0);
+        }
+        fft.inverse(kernel, impulse_response);
+
+        // Both the design IFFT and the IFFT in `process()` scale by the FFT size
+        let delay = Self::latency(window_size);
+        let taps = 2 * delay + 1;
+        let gain = 1.0 / (window_size as f32 * window_size as f32);
+        impulse_response.rotate_right(delay);
+        for (i, sample) in impulse_response.iter_mut().enumerate() {
+            let window = if i < taps {
+                0.5 - 0.5 * (TAU * i as f32 / (taps - 1) as f32).cos()
+            } else {
+                0.0
+            };
+            *sample *= window * gain;
+        }
+        fft.forward(impulse_response, kernel);
+    }
+
+    /// Filter one block in place. `spectrum` is used as scratch space and must be
+    /// `block.len() / 2 + 1` bins long.
+    pub fn process(
+        &mut self,
+        channel: usize,
+        block: &mut [f32],
+        spectrum: &mut [Complex32],
+        fft: &mut FftPlans,
+    ) {
+        let window_size = block.len();
+        let hop = window_size / 2;
+        let kernel = &self.kernel[..spectrum.len()];
+        let segment = &mut self.segment[..window_size];
+        self.active = true;
+
+        self.overlap_add.start_block(channel, window_size);
+        for segment_idx in 0..2 {
+            segment[..hop].copy_from_slice(&block[segment_idx * hop..(segment_idx + 1) * hop]);
+            segment[hop..].fill(0.0);
+            fft.forward(segment, spectrum);
+            for (c, k) in spectrum.iter_mut().zip(kernel) {
+                *c *= k;
+            }
+            fft.inverse(spectrum, segment);
+            self.overlap_add.add(channel, segment_idx * hop, segment);
+        }
+        self.overlap_add.finish_block(channel, block);
+    }
+
+    /// Clear the tails, for when the filter is turned off or the FFT size changes.
+    pub fn reset(&mut self) {
+        if !self.active {
+            return;
+        }
+        self.active = false;
+        self.overlap_add.reset();
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+
+use nih_plug::prelude::{Enum, ParamPtr, Params};
+use nih_plug::util::db_to_gain;
+use serde::{Deserialize, Serialize};
+
+use crate::cpr::CprShape;
+use crate::freeze::FreezePhaseMode;
+use crate::tempo_sync::SyncDivision;
+use crate::PrismatineParams;
+
+/// The category user presets are saved under.
+pub const USER_CATEGORY: &str = "User";
+
+/// A snapshot of the plugin's parameters, either compiled in or saved by the user as JSON.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    pub category: String,
+    /// Plain parameter values by parameter ID. Parameters missing here are set to their defaults,
+    /// so presets stay complete when new parameters are added.
+    pub values: BTreeMap<String, f32>,
+    /// The persistent fields like the user wavetable, as serialized by
+    /// [`Params::serialize_fields()`]. The editor's own fields are left out.
+    #[serde(default)]
+    pub fields: BTreeMap<String, String>,
+}
+
+impl Preset {
+    /// Capture the current parameter values.
+    pub fn capture(params: &PrismatineParams, name: impl Into<String>, category: impl Into<String>) -> Self {
+        let values = params
+            .param_map()
+            .into_iter()
+            // SAFETY: The pointers come from `params`, which outlives this loop
+            .map(|(id, ptr, _)| (id, unsafe { ptr.unmodulated_plain_value() }))
+            .collect();
+        let fields = params
+            .serialize_fields()
+            .into_iter()
+            .filter(|(key, _)| is_preset_field(key))
+            .collect();
+
+        Preset {
+            name: name.into(),
+            category: category.into(),
+            values,
+            fields,
+        }
+    }
+
+    /// The normalized value of every parameter in `params` for this preset. These still need to be
+    /// sent to the host, after which [`Preset::apply_fields()`] restores the rest.
+    pub fn normalized_values(&self, params: &PrismatineParams) -> Vec<(ParamPtr, f32)> {
+        params
+            .param_map()
+            .into_iter()
+            .map(|(id, ptr, _)| {
+                // SAFETY: The pointers come from `params`, which outlives this loop
+                let normalized = unsafe {
+                    match self.values.get(&id) {
+                        Some(plain) => ptr.preview_normalized(*plain),
+                        None => ptr.default_normalized_value(),
+                    }
+                };
+                (ptr, normalized)
+            })
+            .collect()
+    }
+
+    /// Restore the persistent fields that aren't parameters. Fields the preset doesn't have, like
+    /// the wavetable and the morph targets in the factory presets, are reset to their defaults.
+    pub fn apply_fields(&self, params: &PrismatineParams) {
+        let mut fields = default_fields();
+        fields.extend(self.fields.clone());
+        params.deserialize_fields(&fields);
+        params.cpr_wavetable_changed.store(true, Ordering::Release);
+
+        params.morph_targets.write().unwrap().rebuild_table(params);
+        params.morph_targets_changed.store(true, Ordering::Release);
+    }
+
+    /// The name of the file the preset is saved to in [`user_preset_dir()`]. Names that only
+    /// differ in characters that can't be used in file names end up in the same file.
+    pub fn file_name(&self) -> String {
+        let stem: String = self
+            .name
+            .chars()
+            .map(|c| if c.is_alphanumeric() || c == '-' || c == ' ' { c } else { '_' })
+            .collect();
+        format!("{}.json", stem.trim())
+    }
+}
+
+/// Whether a persistent field belongs in presets. The editor's size, scale and theme don't, and
+/// neither do the comparison snapshots.
+fn is_preset_field(key: &str) -> bool {
+    !key.starts_with("editor-") && key != "snapshots"
+}
+
+/// The preset fields of freshly created parameters.
+fn default_fields() -> BTreeMap<String, String> {
+    PrismatineParams::default()
+        .serialize_fields()
+        .into_iter()
+        .filter(|(key, _)| is_preset_field(key))
+        .collect()
+}
+
+/// A factory preset from parameter IDs and plain values.
+fn factory_preset(category: &str, name: &str, values: &[(&str, f32)]) -> Preset {
+    Preset {
+        name: name.to_owned(),
+        category: category.to_owned(),
+        values: values.iter().map(|(id, value)| (id.to_string(), *value)).collect(),
+        fields: BTreeMap::new(),
+    }
+}
+
+/// The plain value of an enum parameter.
+fn variant<T: Enum>(value: T) -> f32 {
+    value.to_index() as f32
+}
+
+/// The presets compiled into the plugin.
+pub fn factory_presets() -> Vec<Preset> {
+    vec![
+        factory_preset("Clean", "Init", &[]),
+        factory_preset(
+            "Warmth",
+            "Subtle Warmth",
+            &[
+                ("phase_gain", db_to_gain(6.0)),
+                ("I_c", db_to_gain(-6.0)),
+                ("emphasis_freq", 3000.0),
+                ("emphasis_amount", 6.0),
+                ("post_eq", 1.0),
+                ("high_cut", 12000.0),
+            ],
+        ),
+        factory_preset(
+            "Warmth",
+            "Tape Glow",
+            &[
+                ("phase_gain", db_to_gain(3.0)),
+                ("cpr_shape", variant(CprShape::Skewed)),
+                ("cpr_amount", 0.3),
+                ("post_eq", 1.0),
+                ("tilt", -2.0),
+            ],
+        ),
+        factory_preset(
+            "Fuzz",
+            "Fuzz",
+            &[
+                ("phase_gain", db_to_gain(30.0)),
+                ("I_c", db_to_gain(-3.0)),
+                ("remove_dc", 1.0),
+            ],
+        ),
+        factory_preset(
+            "Fuzz",
+            "Gated Fuzz",
+            &[
+                ("phase_gain", db_to_gain(42.0)),
+                ("I_c", db_to_gain(-6.0)),
+                ("phase_steps", 8.0),
+                ("phase_hysteresis", 0.3),
+            ],
+        ),
+        factory_preset(
+            "Fuzz",
+            "Split Fuzz",
+            &[
+                ("num_bands", 2.0),
+                ("crossover_1", 250.0),
+                ("band_2_phase_gain", db_to_gain(30.0)),
+                ("band_2_I_c", db_to_gain(-6.0)),
+            ],
+        ),
+        factory_preset(
+            "Drones",
+            "Shapiro Drone",
+            &[
+                ("phase_gain", db_to_gain(24.0)),
+                ("invert_phase", 1.0),
+                ("phase_steps", 16.0),
+                ("feedback", 0.5),
+                ("feedback_delay", 20.0),
+            ],
+        ),
+        factory_preset(
+            "Drones",
+            "Frozen Steps",
+            &[
+                ("phase_gain", db_to_gain(36.0)),
+                ("phase_steps", 4.0),
+                ("freeze", 1.0),
+                ("freeze_phase", variant(FreezePhaseMode::Advancing)),
+            ],
+        ),
+        factory_preset(
+            "Lo-Fi",
+            "Broken Radio",
+            &[
+                ("phase_gain", db_to_gain(36.0)),
+                ("cpr_shape", variant(CprShape::Harmonics)),
+                ("cpr_amount", 0.7),
+                ("lfo_rate", variant(SyncDivision::Quarter)),
+                ("lfo_depth", 90.0),
+                ("post_eq", 1.0),
+                ("low_cut", 300.0),
+                ("high_cut", 4000.0),
+            ],
+        ),
+        factory_preset(
+            "Lo-Fi",
+            "Crushed Phase",
+            &[
+                ("phase_gain", db_to_gain(18.0)),
+                ("phase_steps", 3.0),
+            ],
+        ),
+    ]
+}
+
+/// The directory user presets are stored in, in the platform's per-user data directory.
+pub fn user_preset_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("Prismatine").join("presets"))
+}
+
+/// Read every preset in [`user_preset_dir()`], sorted by name. Files that can't be read are
+/// skipped.
+pub fn load_user_presets() -> Vec<Preset> {
+    let Some(entries) = user_preset_dir().and_then(|dir| fs::read_dir(dir).ok()) else {
+        return Vec::new();
+    };
+
+    let mut presets: Vec<Preset> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|path| match fs::read_to_string(&path).map(|json| serde_json::from_str::<Preset>(&json)) {
+            Ok(Ok(mut preset)) => {
+                preset.category = USER_CATEGORY.to_owned();
+                Some(preset)
+            }
+            Ok(Err(err)) => {
+                nih_plug::nih_log!("Skipping invalid preset {}: {err}", path.display());
+                None
+            }
+            Err(err) => {
+                nih_plug::nih_log!("Could not read preset {}: {err}", path.display());
+                None
+            }
+        })
+        .collect();
+    presets.sort_by(|a, b| a.name.cmp(&b.name));
+
+    presets
+}
+
+/// Write a preset to [`user_preset_dir()`], replacing any preset with the same file name.
+pub fn save_user_preset(preset: &Preset) -> io::Result<PathBuf> {
+    let dir = user_preset_dir()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no user data directory"))?;
+    fs::create_dir_all(&dir)?;
+
+    let path = dir.join(preset.file_name());
+    let json = serde_json::to_string_pretty(preset).map_err(io::Error::other)?;
+    fs::write(&path, json)?;
+
+    Ok(path)
+}
@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use nih_plug::prelude::AtomicF32;
+
+/// A fixed size, lock-free ring buffer for sending values from the audio thread to the editor.
+/// There's a single writer, readers get the most recent values and never block the writer. A
+/// reader racing the writer may see a few values from the next lap, which is fine for displays.
+pub struct AtomicRingBuffer<const N: usize> {
+    values: [AtomicF32; N],
+    /// The total number of values written so far.
+    write_pos: AtomicUsize,
+}
+
+impl<const N: usize> Default for AtomicRingBuffer<N> {
+    fn default() -> Self {
+        Self {
+            values: std::array::from_fn(|_| AtomicF32::new(0.0)),
+            write_pos: AtomicUsize::new(0),
+        }
+    }
+}
+
+impl<const N: usize> AtomicRingBuffer<N> {
+    /// Append a value. Only call this from a single thread.
+    pub fn push(&self, value: f32) {
+        let pos = self.write_pos.load(Ordering::Relaxed);
+        self.values[pos % N].store(value, Ordering::Relaxed);
+        self.write_pos.store(pos.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Copy the most recent values into `out`, oldest first. If fewer values have been written
+    /// than `out` can hold, the start of `out` is left untouched. Returns the number of values
+    /// that were copied.
+    pub fn read_latest(&self, out: &mut [f32]) -> usize {
+        self.read_delayed(out, 0)
+    }
+
+    /// [`AtomicRingBuffer::read_latest()`], but skipping the `delay` most recent values. Returns
+    /// the number of values that were copied.
+    pub fn read_delayed(&self, out: &mut [f32], delay: usize) -> usize {
+        let end = self.write_pos.load(Ordering::Acquire).saturating_sub(delay);
+        let count = out.len().min(N.saturating_sub(delay)).min(end);
+        let offset = out.len() - count;
+        for (i, value) in out[offset..].iter_mut().enumerate() {
+            let pos = end - count + i;
+            *value = self.values[pos % N].load(Ordering::Relaxed);
+        }
+        count
+    }
+
+    /// The total number of values written so far, useful for telling whether anything new
+    /// arrived.
+    pub fn write_count(&self) -> usize {
+        self.write_pos.load(Ordering::Acquire)
+    }
+
+    pub fn clear(&self) {
+        self.values.iter().for_each(|v| v.store(0.0, Ordering::Relaxed));
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+
+use crate::presets::Preset;
+
+/// The number of A/B/C/D comparison slots.
+pub const NUM_SNAPSHOTS: usize = 4;
+
+/// Parameter snapshots for comparing settings. The active slot is the live state of the plugin,
+/// its snapshot is only updated when switching away from it. Saved with the plugin's state.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshots {
+    active: usize,
+    slots: [Option<Preset>; NUM_SNAPSHOTS],
+}
+
+impl Snapshots {
+    pub fn label(slot: usize) -> char {
+        (b'A' + slot as u8) as char
+    }
+
+    pub fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn is_filled(&self, slot: usize) -> bool {
+        self.slots[slot].is_some()
+    }
+
+    /// Store the live settings in the active slot and make `slot` the active one. Returns the
+    /// settings to load, or `None` if the slot was still empty, in which case it starts out as a
+    /// copy of the current settings.
+    pub fn switch_to(&mut self, slot: usize, current: Preset) -> Option<Preset> {
+        if slot == self.active {
+            return None;
+        }
+
+        self.slots[self.active] = Some(current.clone());
+        self.active = slot;
+        match &self.slots[slot] {
+            Some(snapshot) => Some(snapshot.clone()),
+            None => {
+                self.slots[slot] = Some(current);
+                None
+            }
+        }
+    }
+
+    /// Copy the live settings to another slot, replacing whatever was stored there.
+    pub fn copy_to(&mut self, slot: usize, current: Preset) {
+        if slot != self.active {
+            self.slots[slot] = Some(current);
+        }
+    }
+}
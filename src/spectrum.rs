@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use nih_plug::util::gain_to_db;
+use nih_plug::util::window::hann;
+use realfft::{num_complex::Complex32, RealFftPlanner, RealToComplex};
+
+use crate::metering::{Metering, SCOPE_SIZE};
+
+/// The level shown for empty bins, and the bottom of the analyzer.
+pub const SPECTRUM_FLOOR_DB: f32 = -90.0;
+
+/// How strongly successive spectra are averaged in the analyzer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SpectrumAveraging {
+    Off,
+    Fast,
+    #[default]
+    Medium,
+    Slow,
+}
+
+impl SpectrumAveraging {
+    pub fn next(self) -> Self {
+        match self {
+            SpectrumAveraging::Off => SpectrumAveraging::Fast,
+            SpectrumAveraging::Fast => SpectrumAveraging::Medium,
+            SpectrumAveraging::Medium => SpectrumAveraging::Slow,
+            SpectrumAveraging::Slow => SpectrumAveraging::Off,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            SpectrumAveraging::Off => "averaging: off",
+            SpectrumAveraging::Fast => "averaging: fast",
+            SpectrumAveraging::Medium => "averaging: medium",
+            SpectrumAveraging::Slow => "averaging: slow",
+        }
+    }
+
+    /// How much of the previous spectrum is kept every update.
+    fn weight(self) -> f32 {
+        match self {
+            SpectrumAveraging::Off => 0.0,
+            SpectrumAveraging::Fast => 0.5,
+            SpectrumAveraging::Medium => 0.8,
+            SpectrumAveraging::Slow => 0.93,
+        }
+    }
+}
+
+/// Computes the input and output spectra from the oscilloscope buffers. This runs on the GUI
+/// thread, the audio thread only fills the buffers.
+pub struct SpectrumAnalyzer {
+    plan: Arc<dyn RealToComplex<f32>>,
+    window: Vec<f32>,
+    /// Scales the windowed FFT so a full scale sine reads 0 dB.
+    gain_compensation: f32,
+    time_buffer: Vec<f32>,
+    spectrum_buffer: Vec<Complex32>,
+    /// The averaged input and output spectra in dB.
+    spectra: [Vec<f32>; 2],
+    /// The scope's write count at the last update, so nothing is averaged twice.
+    last_write_count: usize,
+}
+
+impl Default for SpectrumAnalyzer {
+    fn default() -> Self {
+        let plan = RealFftPlanner::new().plan_fft_forward(SCOPE_SIZE);
+        let window = hann(SCOPE_SIZE);
+        let gain_compensation = 2.0 / window.iter().sum::<f32>();
+        let spectrum_buffer = plan.make_output_vec();
+        let num_bins = spectrum_buffer.len();
+
+        Self {
+            time_buffer: plan.make_input_vec(),
+            plan,
+            window,
+            gain_compensation,
+            spectrum_buffer,
+            spectra: [vec![SPECTRUM_FLOOR_DB; num_bins], vec![SPECTRUM_FLOOR_DB; num_bins]],
+            last_write_count: 0,
+        }
+    }
+}
+
+impl SpectrumAnalyzer {
+    /// Analyze the latest input and output blocks if the audio thread wrote anything new.
+    pub fn update(&mut self, metering: &Metering, averaging: SpectrumAveraging) {
+        let write_count = metering.output_scope.write_count();
+        if write_count == self.last_write_count {
+            return;
+        }
+        self.last_write_count = write_count;
+
+        let weight = averaging.weight();
+        for (scope, spectrum) in [&metering.input_scope, &metering.output_scope]
+            .into_iter()
+            .zip(self.spectra.iter_mut())
+        {
+            self.time_buffer.fill(0.0);
+            scope.read_latest(&mut self.time_buffer);
+            for (sample, w) in self.time_buffer.iter_mut().zip(&self.window) {
+                *sample *= w;
+            }
+            // The buffer sizes come from the plan, so this can't fail
+            self.plan
+                .process(&mut self.time_buffer, &mut self.spectrum_buffer)
+                .unwrap();
+
+            for (averaged, bin) in spectrum.iter_mut().zip(&self.spectrum_buffer) {
+                let db = gain_to_db(bin.norm() * self.gain_compensation).max(SPECTRUM_FLOOR_DB);
+                *averaged = *averaged * weight + db * (1.0 - weight);
+            }
+        }
+    }
+
+    /// The level in dB at `frequency` for the input (0) or output (1) spectrum.
+    pub fn level_at(&self, spectrum: usize, frequency: f32, sample_rate: f32) -> f32 {
+        let bins = &self.spectra[spectrum];
+        let position = frequency / sample_rate * SCOPE_SIZE as f32;
+        let idx = (position as usize).min(bins.len() - 2);
+        let frac = (position - idx as f32).clamp(0.0, 1.0);
+        bins[idx] + (bins[idx + 1] - bins[idx]) * frac
+    }
+}
@@ -0,0 +1,75 @@
+use nih_plug::prelude::{Enum, Transport};
+
+/// A note length the phase reset and the phase LFO can be locked to.
+#[derive(Enum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SyncDivision {
+    #[name = "Off"]
+    Off,
+    #[name = "1/16"]
+    Sixteenth,
+    #[name = "1/8"]
+    Eighth,
+    #[name = "1/4"]
+    Quarter,
+    #[name = "1/2"]
+    Half,
+    #[name = "1 Bar"]
+    Bar,
+    #[name = "2 Bars"]
+    TwoBars,
+    #[name = "4 Bars"]
+    FourBars,
+}
+
+impl SyncDivision {
+    /// The length of the division in quarter notes, or `None` when syncing is off.
+    pub fn length_beats(self, beats_per_bar: f64) -> Option<f64> {
+        match self {
+            SyncDivision::Off => None,
+            SyncDivision::Sixteenth => Some(0.25),
+            SyncDivision::Eighth => Some(0.5),
+            SyncDivision::Quarter => Some(1.0),
+            SyncDivision::Half => Some(2.0),
+            SyncDivision::Bar => Some(beats_per_bar),
+            SyncDivision::TwoBars => Some(beats_per_bar * 2.0),
+            SyncDivision::FourBars => Some(beats_per_bar * 4.0),
+        }
+    }
+}
+
+/// The host's song position for the current buffer.
+pub struct TransportClock {
+    /// The position at the start of the buffer, in quarter notes.
+    beat: f64,
+    beats_per_sample: f64,
+    pub beats_per_bar: f64,
+}
+
+impl TransportClock {
+    /// Returns `None` if the transport isn't playing or the host doesn't provide a position and
+    /// tempo.
+    pub fn from_transport(transport: &Transport, sample_rate: f32) -> Option<Self> {
+        if !transport.playing {
+            return None;
+        }
+        let tempo = transport.tempo?;
+        let beat = transport.pos_beats()?;
+        let beats_per_bar = match (transport.time_sig_numerator, transport.time_sig_denominator) {
+            (Some(numerator), Some(denominator)) if denominator > 0 => {
+                numerator as f64 * 4.0 / denominator as f64
+            }
+            _ => 4.0,
+        };
+
+        Some(Self {
+            beat,
+            beats_per_sample: tempo / 60.0 / sample_rate as f64,
+            beats_per_bar,
+        })
+    }
+
+    /// The song position `sample_idx` samples into the buffer.
+    pub fn beat_at(&self, sample_idx: usize) -> f64 {
+        self.beat + sample_idx as f64 * self.beats_per_sample
+    }
+}
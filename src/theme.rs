@@ -0,0 +1,80 @@
+use nih_plug_iced::theme::Palette;
+use nih_plug_iced::{Color, Theme};
+use serde::{Deserialize, Serialize};
+
+/// The editor's colour scheme, saved with the plugin's state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EditorTheme {
+    /// Cyan on navy displays in a dark window.
+    #[default]
+    Dark,
+    /// Dark LCD style displays in a light window.
+    Light,
+    /// Yellow on black everywhere, without relying on telling hues apart.
+    HighContrast,
+}
+
+impl EditorTheme {
+    pub fn next(self) -> Self {
+        match self {
+            EditorTheme::Dark => EditorTheme::Light,
+            EditorTheme::Light => EditorTheme::HighContrast,
+            EditorTheme::HighContrast => EditorTheme::Dark,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            EditorTheme::Dark => "dark",
+            EditorTheme::Light => "light",
+            EditorTheme::HighContrast => "high contrast",
+        }
+    }
+
+    /// The background of the seven segment displays and the visualizers.
+    pub fn display_background(self) -> Color {
+        match self {
+            EditorTheme::Dark => Color::from_rgb(0.047, 0.067, 0.09),
+            EditorTheme::Light => Color::from_rgb(0.84, 0.86, 0.8),
+            EditorTheme::HighContrast => Color::BLACK,
+        }
+    }
+
+    /// The lit segments and the traces in the visualizers.
+    pub fn display_foreground(self) -> Color {
+        match self {
+            EditorTheme::Dark => Color::from_rgb(0.69, 1.0, 0.996),
+            EditorTheme::Light => Color::from_rgb(0.1, 0.12, 0.16),
+            EditorTheme::HighContrast => Color::from_rgb(1.0, 0.9, 0.0),
+        }
+    }
+
+    /// The iced theme for the window, the text and the parameter widgets.
+    pub fn iced_theme(self) -> Theme {
+        let palette = match self {
+            EditorTheme::Dark => Palette {
+                background: Color::from_rgb(0.1, 0.11, 0.13),
+                text: Color::from_rgb(0.9, 0.92, 0.94),
+                primary: Color::from_rgb(0.25, 0.7, 0.72),
+                success: Color::from_rgb(0.3, 0.7, 0.45),
+                danger: Color::from_rgb(0.85, 0.35, 0.35),
+            },
+            EditorTheme::Light => Palette {
+                background: Color::from_rgb(0.96, 0.96, 0.94),
+                text: Color::from_rgb(0.08, 0.08, 0.1),
+                primary: Color::from_rgb(0.15, 0.4, 0.75),
+                success: Color::from_rgb(0.15, 0.55, 0.3),
+                danger: Color::from_rgb(0.75, 0.2, 0.2),
+            },
+            EditorTheme::HighContrast => Palette {
+                background: Color::BLACK,
+                text: Color::WHITE,
+                primary: Color::from_rgb(1.0, 0.9, 0.0),
+                success: Color::WHITE,
+                danger: Color::WHITE,
+            },
+        };
+
+        Theme::custom(format!("Prismatine {}", self.label()), palette)
+    }
+}
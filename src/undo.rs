@@ -0,0 +1,125 @@
+use nih_plug::prelude::ParamPtr;
+use nih_plug_iced::widgets::ParamMessage;
+
+use crate::presets::Preset;
+
+/// The number of steps kept, older steps are dropped.
+const MAX_UNDO_STEPS: usize = 100;
+
+/// A single parameter going from one normalized value to another.
+#[derive(Debug, Clone, Copy)]
+pub struct ParamChange {
+    pub param: ParamPtr,
+    pub before: f32,
+    pub after: f32,
+}
+
+/// The preset fields and the preset name a preset load replaced, and the ones it loaded. The
+/// parameters themselves are in the step's [`ParamChange`]s.
+#[derive(Debug, Clone)]
+pub struct PresetChange {
+    pub before: Preset,
+    pub after: Preset,
+}
+
+/// Everything one undo step changed.
+#[derive(Debug, Clone)]
+pub struct UndoStep {
+    pub changes: Vec<ParamChange>,
+    pub preset: Option<PresetChange>,
+}
+
+/// Undo and redo stacks for the parameter edits made in the editor. Every begin/set/end gesture is
+/// one step, so a whole drag is undone at once. Gestures between [`UndoHistory::begin_group()`] and
+/// [`UndoHistory::end_group()`], like everything a preset load changes, are combined into one step.
+#[derive(Default)]
+pub struct UndoHistory {
+    undo: Vec<UndoStep>,
+    redo: Vec<UndoStep>,
+    /// Gestures that have begun but not ended yet.
+    open: Vec<ParamChange>,
+    /// The finished gestures of the current group, or `None` outside of a group.
+    group: Option<Vec<ParamChange>>,
+}
+
+impl UndoHistory {
+    /// Track a parameter message before it's sent to the host.
+    pub fn record(&mut self, message: ParamMessage) {
+        match message {
+            ParamMessage::BeginSetParameter(param) => {
+                // SAFETY: The editor only sends messages for parameters it holds on to
+                let value = unsafe { param.unmodulated_normalized_value() };
+                self.open.retain(|change| change.param != param);
+                self.open.push(ParamChange { param, before: value, after: value });
+            }
+            ParamMessage::SetParameterNormalized(param, value) => {
+                if let Some(change) = self.open.iter_mut().find(|change| change.param == param) {
+                    change.after = value;
+                }
+            }
+            ParamMessage::EndSetParameter(param) => {
+                let Some(pos) = self.open.iter().position(|change| change.param == param) else {
+                    return;
+                };
+                let change = self.open.remove(pos);
+                if change.after == change.before {
+                    return;
+                }
+
+                match &mut self.group {
+                    Some(group) => group.push(change),
+                    None => self.push_step(UndoStep {
+                        changes: vec![change],
+                        preset: None,
+                    }),
+                }
+            }
+        }
+    }
+
+    pub fn begin_group(&mut self) {
+        self.group.get_or_insert_with(Vec::new);
+    }
+
+    /// Finish the group as one step. A preset load passes the fields it replaced, so the step is
+    /// kept even if no parameter changed.
+    pub fn end_group(&mut self, preset: Option<PresetChange>) {
+        if let Some(changes) = self.group.take() {
+            if !changes.is_empty() || preset.is_some() {
+                self.push_step(UndoStep { changes, preset });
+            }
+        }
+    }
+
+    fn push_step(&mut self, step: UndoStep) {
+        self.undo.push(step);
+        if self.undo.len() > MAX_UNDO_STEPS {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// The last step. Restore the `before` values of its changes in reverse order and its preset's
+    /// `before` fields to undo it.
+    pub fn undo(&mut self) -> Option<UndoStep> {
+        let step = self.undo.pop()?;
+        self.redo.push(step.clone());
+        Some(step)
+    }
+
+    /// The last undone step. Restore the `after` values of its changes and its preset's `after`
+    /// fields to redo it.
+    pub fn redo(&mut self) -> Option<UndoStep> {
+        let step = self.redo.pop()?;
+        self.undo.push(step.clone());
+        Some(step)
+    }
+}
@@ -9,4 +9,31 @@ pub fn map_range_linear(in_value: f32, in_start: f32, in_stop: f32, out_start: f
         out = out_stop;
     }
     out
-}
\ No newline at end of file
+}
+
+/// A tiny xorshift PRNG. Good enough for randomizing phases and cheap enough to use on the audio
+/// thread.
+pub struct XorShift32(u32);
+
+impl XorShift32
+{
+    pub fn new(seed: u32) -> Self
+    {
+        //xorshift gets stuck at zero
+        Self(if seed == 0 { 0x9E37_79B9 } else { seed })
+    }
+    pub fn next_u32(&mut self) -> u32
+    {
+        let mut x = self.0;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.0 = x;
+        x
+    }
+    /// A random value in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32
+    {
+        (self.next_u32() >> 8) as f32 / (1u32 << 24) as f32
+    }
+}
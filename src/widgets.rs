@@ -0,0 +1,7 @@
+//! Parameter widgets for the editor, complementing the ones in [`nih_plug_iced::widgets`].
+
+mod param_slider;
+mod param_toggle;
+
+pub use param_slider::ParamSlider;
+pub use param_toggle::ParamToggle;
@@ -0,0 +1,322 @@
+use nih_plug::params::Param;
+use nih_plug_iced::core::alignment;
+use nih_plug_iced::core::keyboard::{self, key};
+use nih_plug_iced::core::layout;
+use nih_plug_iced::core::mouse;
+use nih_plug_iced::core::renderer;
+use nih_plug_iced::core::text::{self, LineHeight, Shaping, Wrapping};
+use nih_plug_iced::core::widget::tree::{self, Tree};
+use nih_plug_iced::core::{
+    event, Border, Clipboard, Element, Event, Layout, Length, Pixels, Point, Rectangle, Shell, Size,
+    Widget,
+};
+use nih_plug_iced::widgets::ParamMessage;
+use nih_plug_iced::{Renderer, Theme};
+
+/// Dragging with Shift held moves the value this much slower.
+const FINE_DRAG_FACTOR: f32 = 0.1;
+
+/// A horizontal slider for a parameter. Next to dragging, which is slowed down while Shift is held,
+/// the value can be reset by double clicking, stepped with the mouse wheel, and typed in after a
+/// right click. Typed values are parsed with the parameter's own string conversion, so `12 dB`
+/// works for a gain parameter. Enter confirms and Escape cancels.
+pub struct ParamSlider<'a, P: Param> {
+    param: &'a P,
+    width: Length,
+    text_size: Pixels,
+}
+
+#[derive(Default)]
+struct State {
+    modifiers: keyboard::Modifiers,
+    drag: Option<Drag>,
+    last_click: Option<mouse::Click>,
+    /// The text typed so far while entering a value.
+    entry: Option<String>,
+}
+
+/// Fine adjustment is relative to where the drag or the last modifier change happened, normal
+/// dragging follows the cursor.
+#[derive(Clone, Copy)]
+struct Drag {
+    anchor_x: f32,
+    anchor_value: f32,
+}
+
+impl<'a, P: Param> ParamSlider<'a, P> {
+    pub fn new(param: &'a P) -> Self {
+        Self {
+            param,
+            width: Length::Fill,
+            text_size: Pixels(14.0),
+        }
+    }
+
+    pub fn width(mut self, width: impl Into<Length>) -> Self {
+        self.width = width.into();
+        self
+    }
+
+    pub fn text_size(mut self, size: impl Into<Pixels>) -> Self {
+        self.text_size = size.into();
+        self
+    }
+
+    /// Map the [`ParamMessage`]s to the editor's message type.
+    pub fn map<Message, F>(self, f: F) -> Element<'a, Message, Theme, Renderer>
+    where
+        Message: 'static,
+        F: Fn(ParamMessage) -> Message + 'static,
+    {
+        Element::from(self).map(f)
+    }
+
+    /// The normalized value for a cursor position, while dragging.
+    fn dragged_value(&self, drag: Drag, fine: bool, x: f32, bounds: Rectangle) -> f32 {
+        if fine {
+            let delta = (x - drag.anchor_x) / bounds.width * FINE_DRAG_FACTOR;
+            (drag.anchor_value + delta).clamp(0.0, 1.0)
+        } else {
+            ((x - bounds.x) / bounds.width).clamp(0.0, 1.0)
+        }
+    }
+
+    /// Set the value outside of a drag, as a complete gesture.
+    fn set_normalized(&self, shell: &mut Shell<'_, ParamMessage>, normalized: f32) {
+        let ptr = self.param.as_ptr();
+        shell.publish(ParamMessage::BeginSetParameter(ptr));
+        shell.publish(ParamMessage::SetParameterNormalized(ptr, normalized));
+        shell.publish(ParamMessage::EndSetParameter(ptr));
+    }
+
+    fn handle_entry_key(
+        &self,
+        state: &mut State,
+        key: keyboard::Key,
+        text: Option<&str>,
+        shell: &mut Shell<'_, ParamMessage>,
+    ) {
+        let Some(entry) = &mut state.entry else {
+            return;
+        };
+
+        match key {
+            keyboard::Key::Named(key::Named::Enter) => {
+                if let Some(normalized) = self.param.string_to_normalized_value(entry.trim()) {
+                    self.set_normalized(shell, normalized);
+                }
+                state.entry = None;
+            }
+            keyboard::Key::Named(key::Named::Escape) => state.entry = None,
+            keyboard::Key::Named(key::Named::Backspace) => {
+                entry.pop();
+            }
+            _ => {
+                if let Some(text) = text {
+                    entry.extend(text.chars().filter(|c| !c.is_control()));
+                }
+            }
+        }
+    }
+}
+
+impl<P: Param> Widget<ParamMessage, Theme, Renderer> for ParamSlider<'_, P> {
+    fn tag(&self) -> tree::Tag {
+        tree::Tag::of::<State>()
+    }
+
+    fn state(&self) -> tree::State {
+        tree::State::new(State::default())
+    }
+
+    fn size(&self) -> Size<Length> {
+        Size::new(self.width, Length::Shrink)
+    }
+
+    fn layout(&self, _tree: &mut Tree, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let height = self.text_size.0 * 1.8;
+        let limits = limits.width(self.width).height(height);
+        layout::Node::new(limits.resolve(self.width, height, Size::ZERO))
+    }
+
+    fn on_event(
+        &mut self,
+        tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, ParamMessage>,
+        _viewport: &Rectangle,
+    ) -> event::Status {
+        let state = tree.state.downcast_mut::<State>();
+        let bounds = layout.bounds();
+        let ptr = self.param.as_ptr();
+
+        match event {
+            Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) => {
+                // Re-anchor the drag so switching between fine and normal dragging doesn't jump
+                if let (Some(drag), Some(position)) = (&mut state.drag, cursor.position()) {
+                    if modifiers.shift() != state.modifiers.shift() {
+                        drag.anchor_x = position.x;
+                        drag.anchor_value = self.param.unmodulated_normalized_value();
+                    }
+                }
+                state.modifiers = modifiers;
+                event::Status::Ignored
+            }
+            Event::Keyboard(keyboard::Event::KeyPressed { key, text, .. }) if state.entry.is_some() => {
+                self.handle_entry_key(state, key, text.as_deref(), shell);
+                event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::ButtonPressed(button)) => {
+                let Some(position) = cursor.position_over(bounds) else {
+                    // Clicking anywhere else cancels the value entry
+                    state.entry = None;
+                    return event::Status::Ignored;
+                };
+
+                match button {
+                    mouse::Button::Left => {
+                        state.entry = None;
+                        let click = mouse::Click::new(position, button, state.last_click);
+                        state.last_click = Some(click);
+                        if matches!(click.kind(), mouse::click::Kind::Double) {
+                            if state.drag.take().is_some() {
+                                shell.publish(ParamMessage::EndSetParameter(ptr));
+                            }
+                            self.set_normalized(shell, self.param.default_normalized_value());
+                        } else {
+                            let drag = Drag {
+                                anchor_x: position.x,
+                                anchor_value: self.param.unmodulated_normalized_value(),
+                            };
+                            // The value only follows the cursor once it moves. Jumping to the
+                            // click would leave an extra undo step before a double click reset
+                            state.drag = Some(drag);
+                            shell.publish(ParamMessage::BeginSetParameter(ptr));
+                        }
+                    }
+                    mouse::Button::Right => state.entry = Some(String::new()),
+                    _ => return event::Status::Ignored,
+                }
+                event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::CursorMoved { position }) => match state.drag {
+                Some(drag) => {
+                    let normalized = self.dragged_value(drag, state.modifiers.shift(), position.x, bounds);
+                    shell.publish(ParamMessage::SetParameterNormalized(ptr, normalized));
+                    event::Status::Captured
+                }
+                None => event::Status::Ignored,
+            },
+            Event::Mouse(mouse::Event::ButtonReleased(mouse::Button::Left)) if state.drag.is_some() => {
+                state.drag = None;
+                shell.publish(ParamMessage::EndSetParameter(ptr));
+                event::Status::Captured
+            }
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) if cursor.is_over(bounds) => {
+                let y = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. } => y,
+                };
+                let current = self.param.unmodulated_normalized_value();
+                let fine = state.modifiers.shift();
+                let normalized = if y > 0.0 {
+                    self.param.next_normalized_step(current, fine)
+                } else if y < 0.0 {
+                    self.param.previous_normalized_step(current, fine)
+                } else {
+                    return event::Status::Ignored;
+                };
+                self.set_normalized(shell, normalized);
+                event::Status::Captured
+            }
+            _ => event::Status::Ignored,
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        let state = tree.state.downcast_ref::<State>();
+        if state.entry.is_some() {
+            mouse::Interaction::Text
+        } else if state.drag.is_some() || cursor.is_over(layout.bounds()) {
+            mouse::Interaction::ResizingHorizontally
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+
+    fn draw(
+        &self,
+        tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        use nih_plug_iced::core::text::Renderer as _;
+        use nih_plug_iced::core::Renderer as _;
+
+        let state = tree.state.downcast_ref::<State>();
+        let bounds = layout.bounds();
+        let palette = theme.extended_palette();
+        let border = Border {
+            color: palette.background.strong.color,
+            width: 1.0,
+            radius: 2.0.into(),
+        };
+
+        renderer.fill_quad(
+            renderer::Quad { bounds, border, ..Default::default() },
+            palette.background.weak.color,
+        );
+
+        let content = match &state.entry {
+            Some(entry) => format!("{entry}|"),
+            None => {
+                let normalized = self.param.modulated_normalized_value();
+                let fill = Rectangle {
+                    width: bounds.width * normalized,
+                    ..bounds
+                };
+                renderer.fill_quad(
+                    renderer::Quad { bounds: fill, border, ..Default::default() },
+                    palette.primary.weak.color,
+                );
+                self.param.normalized_value_to_string(normalized, true)
+            }
+        };
+        renderer.fill_text(
+            text::Text {
+                content,
+                bounds: bounds.size(),
+                size: self.text_size,
+                line_height: LineHeight::default(),
+                font: renderer.default_font(),
+                horizontal_alignment: alignment::Horizontal::Center,
+                vertical_alignment: alignment::Vertical::Center,
+                shaping: Shaping::Basic,
+                wrapping: Wrapping::None,
+            },
+            Point::new(bounds.center_x(), bounds.center_y()),
+            palette.background.weak.text,
+            bounds,
+        );
+    }
+}
+
+impl<'a, P: Param> From<ParamSlider<'a, P>> for Element<'a, ParamMessage, Theme, Renderer> {
+    fn from(widget: ParamSlider<'a, P>) -> Self {
+        Element::new(widget)
+    }
+}
@@ -0,0 +1,201 @@
+use nih_plug::params::Param;
+use nih_plug_iced::core::alignment;
+use nih_plug_iced::core::layout;
+use nih_plug_iced::core::mouse;
+use nih_plug_iced::core::renderer;
+use nih_plug_iced::core::text::{self, LineHeight, Shaping, Wrapping};
+use nih_plug_iced::core::widget::Tree;
+use nih_plug_iced::core::{
+    event, Border, Clipboard, Element, Event, Layout, Length, Pixels, Point, Rectangle, Shell, Size,
+    Widget,
+};
+use nih_plug_iced::widgets::ParamMessage;
+use nih_plug_iced::{Renderer, Theme};
+
+/// A button that steps a discrete parameter, like a [`BoolParam`][nih_plug::prelude::BoolParam]
+/// or an [`EnumParam`][nih_plug::prelude::EnumParam], through its values. A left click selects the
+/// next value and a right click the previous one, wrapping around, so for a boolean both toggle it.
+/// The mouse wheel steps through the values the same way. Every step is sent to the host as a
+/// complete begin/set/end gesture.
+pub struct ParamToggle<'a, P: Param> {
+    param: &'a P,
+    width: Length,
+    text_size: Pixels,
+}
+
+impl<'a, P: Param> ParamToggle<'a, P> {
+    pub fn new(param: &'a P) -> Self {
+        Self {
+            param,
+            width: Length::Fill,
+            text_size: Pixels(14.0),
+        }
+    }
+
+    pub fn width(mut self, width: impl Into<Length>) -> Self {
+        self.width = width.into();
+        self
+    }
+
+    pub fn text_size(mut self, size: impl Into<Pixels>) -> Self {
+        self.text_size = size.into();
+        self
+    }
+
+    /// Map the [`ParamMessage`]s to the editor's message type, like
+    /// [`ParamSlider::map`][nih_plug_iced::widgets::ParamSlider::map].
+    pub fn map<Message, F>(self, f: F) -> Element<'a, Message, Theme, Renderer>
+    where
+        Message: 'static,
+        F: Fn(ParamMessage) -> Message + 'static,
+    {
+        Element::from(self).map(f)
+    }
+
+    /// The number of distinct values the parameter can take, or `None` for continuous parameters.
+    fn num_values(&self) -> Option<usize> {
+        self.param.step_count().map(|steps| steps + 1)
+    }
+
+    /// Whether the parameter is a switch that's currently on, which gets highlighted.
+    fn is_on(&self) -> bool {
+        self.num_values() == Some(2) && self.param.modulated_normalized_value() >= 0.5
+    }
+
+    /// The normalized value `offset` steps away from the current one, wrapping around.
+    fn stepped_value(&self, offset: isize) -> Option<f32> {
+        let num_values = self.num_values()? as isize;
+        let current = (self.param.unmodulated_normalized_value() * (num_values - 1) as f32).round() as isize;
+        let next = (current + offset).rem_euclid(num_values);
+        Some(next as f32 / (num_values - 1) as f32)
+    }
+
+    fn set_normalized(&self, shell: &mut Shell<'_, ParamMessage>, normalized: f32) {
+        let ptr = self.param.as_ptr();
+        shell.publish(ParamMessage::BeginSetParameter(ptr));
+        shell.publish(ParamMessage::SetParameterNormalized(ptr, normalized));
+        shell.publish(ParamMessage::EndSetParameter(ptr));
+    }
+}
+
+impl<P: Param> Widget<ParamMessage, Theme, Renderer> for ParamToggle<'_, P> {
+    fn size(&self) -> Size<Length> {
+        Size::new(self.width, Length::Shrink)
+    }
+
+    fn layout(&self, _tree: &mut Tree, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        let height = self.text_size.0 * 1.8;
+        let limits = limits.width(self.width).height(height);
+        layout::Node::new(limits.resolve(self.width, height, Size::ZERO))
+    }
+
+    fn on_event(
+        &mut self,
+        _tree: &mut Tree,
+        event: Event,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _renderer: &Renderer,
+        _clipboard: &mut dyn Clipboard,
+        shell: &mut Shell<'_, ParamMessage>,
+        _viewport: &Rectangle,
+    ) -> event::Status {
+        if !cursor.is_over(layout.bounds()) {
+            return event::Status::Ignored;
+        }
+
+        let offset = match event {
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) => 1,
+            Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Right)) => -1,
+            Event::Mouse(mouse::Event::WheelScrolled { delta }) => match delta {
+                mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. } if y > 0.0 => 1,
+                mouse::ScrollDelta::Lines { y, .. } | mouse::ScrollDelta::Pixels { y, .. } if y < 0.0 => -1,
+                _ => return event::Status::Ignored,
+            },
+            _ => return event::Status::Ignored,
+        };
+        match self.stepped_value(offset) {
+            Some(normalized) => {
+                self.set_normalized(shell, normalized);
+                event::Status::Captured
+            }
+            None => event::Status::Ignored,
+        }
+    }
+
+    fn mouse_interaction(
+        &self,
+        _tree: &Tree,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+        _renderer: &Renderer,
+    ) -> mouse::Interaction {
+        if cursor.is_over(layout.bounds()) {
+            mouse::Interaction::Pointer
+        } else {
+            mouse::Interaction::default()
+        }
+    }
+
+    fn draw(
+        &self,
+        _tree: &Tree,
+        renderer: &mut Renderer,
+        theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        use nih_plug_iced::core::text::Renderer as _;
+        use nih_plug_iced::core::Renderer as _;
+
+        let bounds = layout.bounds();
+        let palette = theme.extended_palette();
+        let pair = if self.is_on() {
+            palette.primary.base
+        } else if cursor.is_over(bounds) {
+            palette.background.strong
+        } else {
+            palette.background.weak
+        };
+
+        renderer.fill_quad(
+            renderer::Quad {
+                bounds,
+                border: Border {
+                    color: palette.background.strong.color,
+                    width: 1.0,
+                    radius: 2.0.into(),
+                },
+                ..Default::default()
+            },
+            pair.color,
+        );
+        renderer.fill_text(
+            text::Text {
+                content: self
+                    .param
+                    .normalized_value_to_string(self.param.modulated_normalized_value(), true),
+                bounds: bounds.size(),
+                size: self.text_size,
+                line_height: LineHeight::default(),
+                font: renderer.default_font(),
+                horizontal_alignment: alignment::Horizontal::Center,
+                vertical_alignment: alignment::Vertical::Center,
+                shaping: Shaping::Basic,
+                wrapping: Wrapping::None,
+            },
+            Point::new(bounds.center_x(), bounds.center_y()),
+            pair.text,
+            bounds,
+        );
+    }
+}
+
+impl<'a, P: Param> From<ParamToggle<'a, P>> for Element<'a, ParamMessage, Theme, Renderer> {
+    fn from(widget: ParamToggle<'a, P>) -> Self {
+        Element::new(widget)
+    }
+}